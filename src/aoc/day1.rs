@@ -1,4 +1,4 @@
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -8,10 +8,10 @@ pub struct AocDay1 {
 }
 
 impl AocDay for AocDay1 {
-    fn new(input: Vec<String>) -> Self {
-        let mut left = Vec::with_capacity(input.len());
-        let mut right = Vec::with_capacity(input.len());
-        for line in &input {
+    fn new(input: InputLines) -> Self {
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        for line in input {
             let nums: Vec<u32> = line
                 .split_whitespace()
                 .filter_map(|s| s.parse().ok())
@@ -66,13 +66,13 @@ mod test {
 
     #[test]
     fn test_part1() {
-        let day = AocDay1::new(DATA.lines().map(ToString::to_string).collect());
+        let day = AocDay1::new(&mut DATA.lines().map(ToString::to_string));
         assert_eq!(day.part1(), "11");
     }
 
     #[test]
     fn test_part2() {
-        let day = AocDay1::new(DATA.lines().map(ToString::to_string).collect());
+        let day = AocDay1::new(&mut DATA.lines().map(ToString::to_string));
         assert_eq!(day.part2(), "31");
     }
 }
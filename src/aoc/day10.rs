@@ -2,15 +2,16 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::vec;
 use alloc::format;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay10 {
     coords_by_height: [Vec<(i8, i8)>; 10],
 }
 impl AocDay for AocDay10 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut coords_by_height: [Vec<(i8, i8)>; 10] = Default::default();
-        for (r, row) in input.iter().map(|s| s.trim()).filter(|s| !s.is_empty()).enumerate() {
+        for (r, row) in input.filter(|s| !s.trim().is_empty()).enumerate() {
+            let row = row.trim();
             for (c, h) in row.chars().map(|c| c.to_digit(10).unwrap() as u8).enumerate() {
                 coords_by_height[h as usize].push((r as i8, c as i8));
             }
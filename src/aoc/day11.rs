@@ -2,7 +2,7 @@ use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::format;
 use alloc::collections::BTreeMap;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay11 {
     stones: Vec<u32>
@@ -37,8 +37,8 @@ impl AocDay11 {
 }
 
 impl AocDay for AocDay11 {
-    fn new(input: Vec<String>) -> Self {
-        let stones = input[0].split(' ').map(|s| s.parse::<u32>().unwrap()).collect();
+    fn new(input: InputLines) -> Self {
+        let stones = input.next().unwrap_or_default().split(' ').map(|s| s.parse::<u32>().unwrap()).collect();
         Self { stones }
     }
 
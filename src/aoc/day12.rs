@@ -3,16 +3,17 @@ use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::format;
 use alloc::collections::VecDeque;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay12 {
     plots: BTreeMap<char, Vec<(u8, u8)>>
 }
 
 impl AocDay for AocDay12 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut plots : BTreeMap<char, Vec<(u8, u8)>> = BTreeMap::new();
-        for (r, row) in input.iter().map(|s| s.trim()).filter(|s| !s.is_empty()).enumerate() {
+        for (r, row) in input.filter(|s| !s.trim().is_empty()).enumerate() {
+            let row = row.trim();
             for (c, char) in row.chars().enumerate() {
                 plots.entry(char).or_default().push((r as u8, c as u8));
             }
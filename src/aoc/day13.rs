@@ -1,7 +1,7 @@
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::format;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay13 {
     machines: Vec<Machine>,
@@ -47,12 +47,13 @@ impl Machine {
 }
 
 impl AocDay for AocDay13 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut button_a = None;
         let mut button_b = None;
         let mut prize = None;
         let mut machines = Vec::new();
-        for line in input.iter().map(|s| s.trim()) {
+        for line in input {
+            let line = line.trim();
             if line.is_empty() {
                 continue;
             }
@@ -3,7 +3,7 @@ use alloc::string::String;
 use alloc::format;
 use alloc::collections::BTreeSet;
 use crate::debug;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay14 {
     robots: Vec<Robot>,
@@ -28,11 +28,11 @@ const WIDTH: usize = 101;
 const HEIGHT: usize = 103;
 
 impl AocDay for AocDay14 {
-    fn new(input: Vec<String>) -> Self {
-        let robots = input.iter()
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty())
+    fn new(input: InputLines) -> Self {
+        let robots = input
+            .filter(|s| !s.trim().is_empty())
             .map(|s| {
+                let s = s.trim();
                 let (p, v) = s.split_once(' ').unwrap();
                 let (px, py) = p.strip_prefix("p=").unwrap().split_once(',').unwrap();
                 let (vx, vy) = v.strip_prefix("v=").unwrap().split_once(',').unwrap();
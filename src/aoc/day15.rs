@@ -2,7 +2,7 @@ use alloc::collections::BTreeSet;
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::{format, vec};
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay15 {
     map: Map,
@@ -25,11 +25,11 @@ enum Direction {
 }
 
 impl AocDay for AocDay15 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut map = Map::default();
         let mut directions = Vec::new();
 
-        for (r, row) in input.iter().filter(|s| !s.is_empty()).enumerate() {
+        for (r, row) in input.filter(|s| !s.is_empty()).enumerate() {
             if row.starts_with('#') {
                 for (c, char) in row.chars().enumerate() {
                     match char {
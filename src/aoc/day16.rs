@@ -5,7 +5,7 @@ use alloc::vec::Vec;
 use alloc::{format};
 use core::ops::{RangeBounds};
 use crate::debug;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 use crate::aoc::coord::{Coord, Direction};
 
 pub struct AocDay16 {
@@ -15,14 +15,14 @@ pub struct AocDay16 {
 }
 
 impl AocDay for AocDay16 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         crate::debug_heap_size("new");
         let mut walls = BTreeSet::new();
         let mut start = Coord::default();
         let mut end = Coord::default();
         let mut width = 0;
         let mut height = 0;
-        for (r, row) in input.iter().filter(|s| !s.is_empty()).enumerate() {
+        for (r, row) in input.filter(|s| !s.is_empty()).enumerate() {
             height += 1;
             width = width.max(row.len() as u8);
             for (c, char) in row.chars().enumerate() {
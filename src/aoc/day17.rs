@@ -1,7 +1,7 @@
 use alloc::{format};
 use alloc::string::String;
 use alloc::vec::Vec;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 #[derive(Default)]
 pub struct AocDay17 {
@@ -12,7 +12,7 @@ pub struct AocDay17 {
 }
 
 impl AocDay for AocDay17 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut day = AocDay17::default();
         for line in input {
             if let Some(a) = line.strip_prefix("Register A: ") {
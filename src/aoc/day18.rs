@@ -3,15 +3,15 @@ use alloc::string::String;
 use alloc::format;
 use alloc::vec::Vec;
 use crate::debug;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay18 {
     bytes: Vec<(u8, u8)>,
 }
 
 impl AocDay for AocDay18 {
-    fn new(input: Vec<String>) -> Self {
-        let bytes = input.iter()
+    fn new(input: InputLines) -> Self {
+        let bytes = input
             .filter_map(|s| {
                 let (x, y) = s.split_once(',')?;
                 Some((x.parse().ok()?, y.parse().ok()?))
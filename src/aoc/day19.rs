@@ -4,7 +4,7 @@ use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::cmp::Ordering;
 use core::ops::Bound;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay19 {
     available_towels: BTreeSet<String>,
@@ -12,14 +12,13 @@ pub struct AocDay19 {
 }
 
 impl AocDay for AocDay19 {
-    fn new(input: Vec<String>) -> Self {
-        let mut iter = input.into_iter();
-        let available_towels = iter.next().unwrap()
+    fn new(input: InputLines) -> Self {
+        let available_towels = input.next().unwrap()
             .split(", ")
             .map(ToString::to_string)
             .collect();
-        iter.next();
-        let patterns = iter.filter(|s| !s.is_empty()).collect();
+        input.next();
+        let patterns = input.filter(|s| !s.is_empty()).collect();
         Self {
             available_towels,
             patterns,
@@ -1,4 +1,4 @@
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -7,9 +7,8 @@ pub struct AocDay2 {
 }
 
 impl AocDay for AocDay2 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let reports = input
-            .iter()
             .filter(|line| !line.trim().is_empty())
             .filter_map(|line| {
                 line.split_whitespace()
@@ -103,7 +102,7 @@ mod test {
 
     #[test]
     fn test() {
-        let day = AocDay2::new(INPUT.lines().map(ToString::to_string).collect());
+        let day = AocDay2::new(&mut INPUT.lines().map(ToString::to_string));
         assert_eq!(day.part1(), "2");
         assert_eq!(day.part2(), "4");
     }
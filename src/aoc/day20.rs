@@ -2,7 +2,7 @@ use alloc::collections::BTreeSet;
 use alloc::string::String;
 use alloc::{format, vec};
 use alloc::vec::Vec;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay20 {
     start: Coord,
@@ -13,11 +13,11 @@ pub struct AocDay20 {
 type Coord = [u8; 2];
 
 impl AocDay for AocDay20 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut start = None;
         let mut end = None;
         let mut walls = BTreeSet::new();
-        for (y, line) in input.into_iter().enumerate() {
+        for (y, line) in input.enumerate() {
             for (x, char) in line.chars().enumerate() {
                 match char {
                     'S' => start = Some([x as u8, y as u8]),
@@ -3,15 +3,15 @@ use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 use crate::debug;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay21 {
     codes: Vec<String>,
 }
 
 impl AocDay for AocDay21 {
-    fn new(input: Vec<String>) -> Self {
-        Self { codes: input.into_iter().filter(|s| !s.is_empty()).collect() }
+    fn new(input: InputLines) -> Self {
+        Self { codes: input.filter(|s| !s.is_empty()).collect() }
     }
 
     fn part1(&self) -> String {
@@ -2,16 +2,16 @@ use alloc::collections::BTreeMap;
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay22 {
     secrets: Vec<u32>,
 }
 
 impl AocDay for AocDay22 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         Self {
-            secrets: input.iter().filter_map(|s| s.parse().ok()).collect(),
+            secrets: input.filter_map(|s| s.parse().ok()).collect(),
         }
     }
 
@@ -3,7 +3,7 @@ use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cmp::Reverse;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay23 {
     links: BTreeMap<Computer, BTreeSet<Computer>>,
@@ -12,7 +12,7 @@ pub struct AocDay23 {
 type Computer = [char; 2];
 
 impl AocDay for AocDay23 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut this = Self {links: BTreeMap::new()};
         for line in input {
             let chars : Vec<_> = line.chars().collect();
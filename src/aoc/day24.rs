@@ -2,7 +2,7 @@ use alloc::collections::BTreeMap;
 use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 use crate::debug;
 
 pub struct AocDay24 {
@@ -10,7 +10,7 @@ pub struct AocDay24 {
 }
 
 impl AocDay for AocDay24 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut wires = BTreeMap::new();
         for line in input {
             if let Some((wire, value)) = line.split_once(": ") {
@@ -1,7 +1,7 @@
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay25 {
     locks: Vec<[u8; 5]>,
@@ -9,13 +9,13 @@ pub struct AocDay25 {
 }
 
 impl AocDay for AocDay25 {
-    fn new(mut input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut locks = Vec::new();
         let mut keys = Vec::new();
         let mut key_or_lock = None;
         let mut heights = [0; 5];
-        input.push(String::new());
-        for line in input {
+        // an empty line after the last schematic
+        for line in input.chain([String::new()]) {
             if line.is_empty() {
                 match key_or_lock {
                     None => {},
@@ -1,4 +1,4 @@
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -7,9 +7,9 @@ pub struct AocDay3 {
 }
 
 impl AocDay for AocDay3 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         Self {
-            code: input.join("\n"),
+            code: input.collect::<Vec<_>>().join("\n"),
         }
     }
 
@@ -60,7 +60,6 @@ impl AocDay for AocDay3 {
 mod test {
     use crate::aoc::AocDay;
     use crate::aoc::day3::AocDay3;
-    use alloc::vec;
 
     const INPUT: &'static str =
         "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
@@ -69,9 +68,9 @@ mod test {
 
     #[test]
     fn test() {
-        let part1 = AocDay3::new(vec![INPUT.into()]);
+        let part1 = AocDay3::new(&mut [INPUT.into()].into_iter());
         assert_eq!(part1.part1(), "161");
-        let part2 = AocDay3::new(vec![INPUT2.into()]);
+        let part2 = AocDay3::new(&mut [INPUT2.into()].into_iter());
         assert_eq!(part2.part2(), "48");
     }
 }
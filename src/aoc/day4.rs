@@ -1,4 +1,4 @@
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -17,9 +17,8 @@ impl AocDay4 {
 }
 
 impl AocDay for AocDay4 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let letters = input
-            .into_iter()
             .filter_map(|line| {
                 if line.is_empty() {
                     None
@@ -163,7 +162,7 @@ MXMXAXMASX";
 
     #[test]
     fn test() {
-        let day = AocDay4::new(INPUT.lines().map(ToString::to_string).collect());
+        let day = AocDay4::new(&mut INPUT.lines().map(ToString::to_string));
         assert_eq!(day.part1(), "18");
         assert_eq!(day.part2(), "9");
     }
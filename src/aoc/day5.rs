@@ -1,4 +1,4 @@
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -43,8 +43,8 @@ impl AocDay5 {
 }
 
 impl AocDay for AocDay5 {
-    fn new(input: Vec<String>) -> Self {
-        let mut lines = input.into_iter().peekable();
+    fn new(input: InputLines) -> Self {
+        let mut lines = input.peekable();
         while lines.peek().is_some_and(|s| s.is_empty()) {
             lines.next();
         }
@@ -126,7 +126,7 @@ mod test {
 
     #[test]
     fn test() {
-        let day = AocDay5::new(INPUT.lines().map(ToString::to_string).collect());
+        let day = AocDay5::new(&mut INPUT.lines().map(ToString::to_string));
         assert_eq!(day.part1(), "143");
         assert_eq!(day.part2(), "123");
     }
@@ -1,4 +1,4 @@
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 use alloc::collections::BTreeSet;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
@@ -164,10 +164,10 @@ impl<M: Map> Map for OneMoreWall<'_, M> {
 }
 
 impl AocDay for AocDay6 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut start = (0, 0, Direction::Top);
         let mut map = Vec::new();
-        for (y, line) in input.iter().filter(|l| !l.is_empty()).enumerate() {
+        for (y, line) in input.filter(|l| !l.is_empty()).enumerate() {
             map.push(Vec::new());
             for (x, c) in line.chars().enumerate() {
                 let wall = match c {
@@ -250,7 +250,7 @@ mod test {
 ......#...";
     #[test]
     fn test() {
-        let day = AocDay6::new(INPUT.lines().map(ToString::to_string).collect());
+        let day = AocDay6::new(&mut INPUT.lines().map(ToString::to_string));
         assert_eq!(day.part1(), "41");
         assert_eq!(day.part2(), "6");
     }
@@ -1,15 +1,15 @@
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::format;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay7 {
     equations: Vec<Equation>,
 }
 
 impl AocDay for AocDay7 {
-    fn new(input: Vec<String>) -> Self {
-        let equations = input.into_iter().filter_map(|s| Equation::from_str(&s)).collect();
+    fn new(input: InputLines) -> Self {
+        let equations = input.filter_map(|s| Equation::from_str(&s)).collect();
         Self { equations }
     }
 
@@ -2,7 +2,7 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::format;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay8 {
     width: usize,
@@ -11,11 +11,12 @@ pub struct AocDay8 {
 }
 
 impl AocDay for AocDay8 {
-    fn new(input: Vec<String>) -> Self {
+    fn new(input: InputLines) -> Self {
         let mut antennas : BTreeMap<char, Vec<(usize, usize)>> = BTreeMap::new();
         let mut width = 0;
         let mut height = 0;
-        for (i, row) in input.iter().map(|s| s.trim()).filter(|s| !s.is_empty()).enumerate() {
+        for (i, row) in input.filter(|s| !s.trim().is_empty()).enumerate() {
+            let row = row.trim();
             if i == 0 {
                 width = row.len();
             }
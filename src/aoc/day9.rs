@@ -1,15 +1,15 @@
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::format;
-use crate::aoc::AocDay;
+use crate::aoc::{AocDay, InputLines};
 
 pub struct AocDay9 {
     layout: Vec<u8>,
 }
 
 impl AocDay for AocDay9 {
-    fn new(input: Vec<String>) -> Self {
-        let layout = input.iter().flat_map(|s| s.trim().chars())
+    fn new(input: InputLines) -> Self {
+        let layout = input.flat_map(|s| s.trim().chars().collect::<Vec<_>>())
             .map(|c| format!("{c}").parse().unwrap())
             .collect();
         Self {layout}
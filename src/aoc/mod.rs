@@ -1,7 +1,8 @@
-use aoc_pico::shell::{SyncCommand, SyncRunningCommand};
+use aoc_pico::shell::{Args, CommandHelp, InputLines, Output, SyncCommand, SyncRunningCommand};
 use alloc::boxed::Box;
+use alloc::collections::VecDeque;
 use alloc::format;
-use alloc::string::String;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use crate::aoc::day1::AocDay1;
 use crate::aoc::day2::AocDay2;
@@ -57,9 +58,19 @@ mod day23;
 mod day24;
 mod day25;
 
+#[derive(Clone)]
 pub struct AocRunner;
 
 impl AocRunner {
+    pub const HELP: CommandHelp = CommandHelp {
+        summary: "solve a day of Advent of Code",
+        usage: "aoc <day> [<]",
+        args: &[
+            ("<day>", "day number, 0 only counts input lines and columns"),
+            ("<", "read the puzzle input until Ctrl-D"),
+        ],
+    };
+
     pub fn new() -> Self {
         Self
     }
@@ -71,33 +82,44 @@ impl Default for AocRunner {
     }
 }
 
-struct ErrRunningCommand(Option<String>);
+/// Error message, then a failure exit status
+struct ErrRunningCommand(VecDeque<Output>);
+
+impl ErrRunningCommand {
+    fn new(err: String) -> Self {
+        Self([Output::Error(err), Output::Exit(2)].into())
+    }
+}
+
 impl SyncRunningCommand for ErrRunningCommand {
-    fn next_sync(&mut self) -> Option<String> {
-        self.0.take()
+    fn next_sync(&mut self) -> Option<Output> {
+        self.0.pop_front()
     }
 }
 
 impl SyncCommand for AocRunner {
     type RunningCommand = Box<dyn SyncRunningCommand>;
-    fn exec_sync(&self, args: Vec<String>, input: Vec<String>) -> Self::RunningCommand {
-        let day = args
-            .first()
-            .map(String::as_str)
-            .unwrap_or("0")
-            .parse::<usize>();
-        if day.is_err() {
-            return Box::new(ErrRunningCommand(Some(String::from("bad day"))));
+    fn exec_sync(&self, args: Args, input: InputLines) -> Self::RunningCommand {
+        let arg = args.get(0).unwrap_or("0");
+        match arg.parse::<usize>() {
+            Ok(day) if day < NB_DAYS => Box::new(DAYS[day](input)),
+            Ok(_) => Box::new(ErrRunningCommand::new(format!("no day {}, days are 0 to {}", arg, NB_DAYS - 1))),
+            Err(_) => Box::new(ErrRunningCommand::new(format!("day {} is not a number", arg))),
         }
-        let day = day.unwrap();
-        if day >= NB_DAYS {
-            return Box::new(ErrRunningCommand(Some(String::from("bad day"))));
+    }
+
+    fn complete(&self, args: &[&str], prefix: &str) -> Vec<String> {
+        if !args.is_empty() {
+            return Vec::new();
         }
-        Box::new(DAYS[day](input))
+        (0..NB_DAYS)
+            .map(|day| day.to_string())
+            .filter(|day| day.starts_with(prefix))
+            .collect()
     }
 }
 
-type AocDayFn = fn(Vec<String>) -> Box<dyn SyncRunningCommand + 'static>;
+type AocDayFn = fn(InputLines) -> Box<dyn SyncRunningCommand + 'static>;
 
 const NB_DAYS: usize = 1 + 25;
 const DAYS: [AocDayFn; NB_DAYS] = [
@@ -133,7 +155,8 @@ trait AocDay: Send + Sized
 where
     Self: 'static,
 {
-    fn new(input: Vec<String>) -> Self;
+    /// Parse the input lines as they are received
+    fn new(input: InputLines) -> Self;
 
     fn part1(&self) -> String {
         String::new()
@@ -142,7 +165,7 @@ where
         String::new()
     }
 
-    fn run(input: Vec<String>) -> Box<dyn SyncRunningCommand> {
+    fn run(input: InputLines) -> Box<dyn SyncRunningCommand> {
         Box::new(RunningAoc(Self::new(input), 0))
     }
 }
@@ -150,42 +173,49 @@ where
 struct RunningAoc<D: AocDay>(D, u8);
 
 impl<D: AocDay> SyncRunningCommand for RunningAoc<D> {
-    fn next_sync(&mut self) -> Option<String> {
+    fn next_sync(&mut self) -> Option<Output> {
         match self.1 {
             0 => {
                 self.1 = 1;
-                Some(String::from("running..."))
+                Some(Output::Line(String::from("running...")))
             }
             1 => {
                 self.1 = 2;
-                Some(format!("Part1: {}", self.0.part1()))
+                Some(Output::Line(format!("Part1: {}", self.0.part1())))
             }
             2 => {
                 self.1 = 3;
-                Some(format!("Part2: {}", self.0.part2()))
+                Some(Output::Line(format!("Part2: {}", self.0.part2())))
             }
             _ => None,
         }
     }
+
+    fn cancel_sync(&mut self) {
+        self.1 = 3;
+    }
 }
 
 struct TestDay0 {
-    input: Vec<String>,
+    lines: usize,
+    max_cols: usize,
 }
 
 impl AocDay for TestDay0 {
-    fn new(input: Vec<String>) -> Self {
-        Self { input }
+    fn new(input: InputLines) -> Self {
+        let mut day = Self { lines: 0, max_cols: 0 };
+        for line in input {
+            day.lines += 1;
+            day.max_cols = day.max_cols.max(line.len());
+        }
+        day
     }
 
     fn part1(&self) -> String {
-        format!("lines={}", self.input.len())
+        format!("lines={}", self.lines)
     }
 
     fn part2(&self) -> String {
-        format!(
-            "max-cols={}",
-            self.input.iter().map(|l| l.len()).max().unwrap_or_default()
-        )
+        format!("max-cols={}", self.max_cols)
     }
 }
@@ -0,0 +1,342 @@
+//! Core0 side of the commands run on core1, one at a time for all the sessions
+//!
+//! Generic over the fifo between the cores, so that sessions racing for core1 are also run on Linux.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use aoc_pico::shell::{Args, Command, Output, RunningCommand, SyncCommand};
+use core::future::{Future, poll_fn, ready};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+/// Fifo between the cores, carrying the addresses of boxed values
+pub trait Fifo {
+    /// Address sent by the other core, `None` while there is none
+    fn read(&mut self) -> Option<usize>;
+    fn write_blocking(&mut self, addr: usize);
+}
+
+/// Set by core0 on Ctrl-C, checked by core1 before and after computing each output line
+pub static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Input lines kept while waiting for core1, the next ones being left unread by the console
+const WAITING_LINES: usize = 16;
+
+/// Set while a session has a command on core1, the commands of the other sessions waiting for it
+///
+/// Only the sessions on core0 use it, all polled by the same loop.
+static BUSY: AtomicBool = AtomicBool::new(false);
+
+pub struct MulticoreProxy<F, C> {
+    fifo: *mut F,
+    // copy of the runner, only used on core0 for completion
+    completer: C,
+}
+
+unsafe impl<F, C: Send> Send for MulticoreProxy<F, C> {}
+
+impl<F, C> MulticoreProxy<F, C> {
+    pub fn new(fifo: &'static mut F, completer: C) -> Self {
+        Self { fifo, completer }
+    }
+}
+
+impl<F: Fifo + 'static, C: SyncCommand> Command for MulticoreProxy<F, C> {
+    fn exec(&self, args: Args) -> Box<dyn RunningCommand> {
+        Box::new(MulticoreReceiver::new(self.fifo, args))
+    }
+
+    fn complete(&self, args: &[&str], prefix: &str) -> Vec<String> {
+        self.completer.complete(args, prefix)
+    }
+}
+
+pub struct MulticoreReceiver<F: Fifo> {
+    fifo: *mut F,
+    // until core1 is free, with the input lines received meanwhile
+    waiting: Option<(Args, Vec<Option<String>>)>,
+    input_ended: bool,
+    finished: bool,
+}
+
+unsafe impl<F: Fifo> Send for MulticoreReceiver<F> {}
+
+impl<F: Fifo> MulticoreReceiver<F> {
+    fn new(fifo: *mut F, args: Args) -> Self {
+        Self {
+            fifo,
+            waiting: Some((args, Vec::new())),
+            input_ended: false,
+            finished: false,
+        }
+    }
+
+    /// Only used once the command is sent, when no other session uses the fifo
+    fn fifo(&mut self) -> &mut F {
+        unsafe { &mut *self.fifo }
+    }
+
+    /// Send the command to core1 if no other session uses it, false while waiting for it
+    fn start(&mut self) -> bool {
+        if self.waiting.is_some() && !self.core1_free() {
+            return false;
+        }
+        let Some((args, input)) = self.waiting.take() else {
+            return true;
+        };
+        BUSY.store(true, Ordering::Relaxed);
+        self.fifo().write_blocking(Box::into_raw(Box::new(args)) as usize);
+        for line in input {
+            self.send(line);
+        }
+        true
+    }
+
+    /// Whether core1 is free, dropping what it still outputs for a cancelled command until it is done
+    fn core1_free(&mut self) -> bool {
+        if BUSY.load(Ordering::Relaxed) {
+            return false;
+        }
+        while CANCELLED.load(Ordering::Relaxed) {
+            let Some(addr) = self.fifo().read() else {
+                return false;
+            };
+            if unsafe { Box::from_raw(addr as *mut Option<Output>) }.is_none() {
+                CANCELLED.store(false, Ordering::Relaxed);
+            }
+        }
+        true
+    }
+
+    fn send(&mut self, line: Option<String>) {
+        // core1 reads every line until the end of input, even when the command does not
+        let boxed = Box::new(line);
+        self.fifo().write_blocking(Box::into_raw(boxed) as usize);
+    }
+}
+
+impl<F: Fifo> RunningCommand for MulticoreReceiver<F> {
+    fn next(&mut self) -> Pin<Box<dyn Future<Output = Option<Output>> + Send + '_>> {
+        if self.finished {
+            return Box::pin(ready(None));
+        }
+        Box::pin(poll_fn(|cx| {
+            let item = match self.start() {
+                true => self.fifo().read(),
+                false => None,
+            };
+            match item {
+                Some(addr) => {
+                    let item = unsafe { Box::from_raw(addr as *mut Option<Output>) };
+                    if item.is_none() {
+                        self.finished = true;
+                        BUSY.store(false, Ordering::Relaxed);
+                    }
+                    Poll::Ready(*item)
+                }
+                None => {
+                    // no interrupt for the fifo nor for core1 being free, poll again on next loop
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            }
+        }))
+    }
+
+    fn input(&mut self, line: Option<String>) {
+        if self.input_ended {
+            return;
+        }
+        self.input_ended = line.is_none();
+        match &mut self.waiting {
+            Some((_, input)) => input.push(line),
+            None => self.send(line),
+        }
+    }
+
+    fn wants_input(&self) -> bool {
+        self.waiting.as_ref().is_none_or(|(_, input)| input.len() < WAITING_LINES)
+    }
+
+    fn cancel(&mut self) {
+        if self.waiting.take().is_some() {
+            // never sent to core1
+            self.finished = true;
+            return;
+        }
+        let running = !core::mem::replace(&mut self.finished, true);
+        if running {
+            CANCELLED.store(true, Ordering::Relaxed);
+        }
+        self.input(None);
+        if running {
+            // the next command waits for core1 to acknowledge the cancellation
+            BUSY.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Dropped without being cancelled, as with the session of a client disconnecting, the command still
+/// frees core1 for the other sessions
+impl<F: Fifo> Drop for MulticoreReceiver<F> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.cancel();
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+    use crate::aoc::AocRunner;
+    use alloc::collections::VecDeque;
+    use alloc::format;
+    use alloc::rc::Rc;
+    use aoc_pico::shell::{Commands, Console, ConsoleConfig, ConsoleDriver, InputParser, MutexQueue};
+
+    extern crate std;
+
+    /// Held by the tests using core1, its state being shared as on the pico
+    static CORE1: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// Both directions of the fifo, core1 being played by the test
+    #[derive(Default)]
+    struct TestFifo {
+        to_core1: VecDeque<usize>,
+        to_core0: VecDeque<usize>,
+    }
+
+    impl Fifo for TestFifo {
+        fn read(&mut self) -> Option<usize> {
+            self.to_core0.pop_front()
+        }
+
+        fn write_blocking(&mut self, addr: usize) {
+            self.to_core1.push_back(addr);
+        }
+    }
+
+    impl TestFifo {
+        /// Day of the command received by core1, after all its input lines
+        fn received(&mut self) -> Option<String> {
+            let addr = self.to_core1.pop_front()?;
+            let args = *unsafe { Box::from_raw(addr as *mut Args) };
+            while let Some(addr) = self.to_core1.pop_front() {
+                if unsafe { Box::from_raw(addr as *mut Option<String>) }.is_none() {
+                    break;
+                }
+            }
+            args.get(0).map(String::from)
+        }
+
+        /// Input lines received by core1 so far
+        fn lines(&mut self) -> Vec<Option<String>> {
+            let lines = self.to_core1.drain(..);
+            lines.map(|addr| *unsafe { Box::from_raw(addr as *mut Option<String>) }).collect()
+        }
+
+        fn output(&mut self, output: Option<Output>) {
+            self.to_core0.push_back(Box::into_raw(Box::new(output)) as usize);
+        }
+    }
+
+    #[test]
+    fn test_sessions_race() {
+        let _core1 = CORE1.lock();
+        // shared by the sessions and by core1, as the fifo of the pico
+        let fifo: *mut TestFifo = Box::into_raw(Box::default());
+        let core1 = || unsafe { &mut *fifo };
+        let mut commands = Commands::new();
+        commands.add("aoc", AocRunner::HELP, MulticoreProxy::new(core1(), AocRunner::new()));
+        let commands = Rc::new(commands);
+        let (queue1, queue2) = (MutexQueue::new(), MutexQueue::new());
+        let console1 = Console::new(InputParser::new(queue1.clone()), commands.clone(), ConsoleConfig::default());
+        let console2 = Console::new(InputParser::new(queue2.clone()), commands, ConsoleConfig::default());
+        let (mut session1, mut session2) = (ConsoleDriver::new(console1), ConsoleDriver::new(console2));
+
+        // the first session gets core1, the second one waits for it
+        queue1.push(b"aoc 1\r".to_vec());
+        assert_eq!(session1.run(), b"aoc 1\r\n> ");
+        queue2.push(b"aoc 2\r".to_vec());
+        assert_eq!(session2.run(), b"aoc 2\r\n> ");
+        assert_eq!(core1().received().as_deref(), Some("1"));
+        assert_eq!(core1().received(), None);
+        core1().output(Some(Output::Line("one".into())));
+        core1().output(None);
+        assert_eq!(session2.run(), b"");
+        assert_eq!(session1.run(), b"\r\n> one\r\n$ ");
+        assert_eq!(session2.run(), b"");
+        assert_eq!(core1().received().as_deref(), Some("2"));
+
+        // once cancelled, core1 is free after its last output for the cancelled command
+        queue2.push(b"\x03".to_vec());
+        assert_eq!(session2.run(), b"^C\r\n$ ");
+        queue1.push(b"aoc 3\r".to_vec());
+        assert_eq!(session1.run(), b"aoc 3\r\n> ");
+        assert_eq!(core1().received(), None);
+        core1().output(Some(Output::Line("two".into())));
+        assert_eq!(session1.run(), b"");
+        assert_eq!(core1().received(), None);
+        core1().output(None);
+        assert_eq!(session1.run(), b"");
+        assert_eq!(core1().received().as_deref(), Some("3"));
+        core1().output(None);
+        assert_eq!(session1.run(), b"\r\n$ ");
+
+        // a session dropped with its command on core1 leaves it to the other one
+        queue1.push(b"aoc 4\r".to_vec());
+        assert_eq!(session1.run(), b"aoc 4\r\n> ");
+        queue2.push(b"aoc 5\r".to_vec());
+        assert_eq!(session2.run(), b"aoc 5\r\n> ");
+        assert_eq!(core1().received().as_deref(), Some("4"));
+        drop(session1);
+        assert_eq!(session2.run(), b"");
+        assert_eq!(core1().received(), None);
+        core1().output(None);
+        assert_eq!(session2.run(), b"");
+        assert_eq!(core1().received().as_deref(), Some("5"));
+        core1().output(None);
+        assert_eq!(session2.run(), b"\r\n$ ");
+    }
+
+    #[test]
+    fn test_input_waiting() {
+        let _core1 = CORE1.lock();
+        let fifo: *mut TestFifo = Box::into_raw(Box::default());
+        let core1 = || unsafe { &mut *fifo };
+        let mut commands = Commands::new();
+        commands.add("aoc", AocRunner::HELP, MulticoreProxy::new(core1(), AocRunner::new()));
+        let commands = Rc::new(commands);
+        let (queue1, queue2) = (MutexQueue::new(), MutexQueue::new());
+        let console1 = Console::new(InputParser::new(queue1.clone()), commands.clone(), ConsoleConfig::default());
+        let console2 = Console::new(InputParser::new(queue2.clone()), commands, ConsoleConfig::default());
+        let (mut session1, mut session2) = (ConsoleDriver::new(console1), ConsoleDriver::new(console2));
+
+        // input beyond what is kept while waiting for core1 is left unread
+        queue1.push(b"aoc 1\r".to_vec());
+        session1.run();
+        queue2.push(b"aoc 2 <\r".to_vec());
+        session2.run();
+        for i in 0..20 {
+            queue2.push(format!("{i}\r").into_bytes());
+        }
+        let echo = |lines: core::ops::Range<usize>| lines.map(|i| format!("{i}\r\n< ")).collect::<String>();
+        assert_eq!(session2.run(), echo(0..WAITING_LINES).as_bytes());
+        assert_eq!(core1().received().as_deref(), Some("1"));
+        assert_eq!(core1().received(), None);
+
+        // read again once the command is sent to core1
+        core1().output(None);
+        assert_eq!(session1.run(), b"\r\n$ ");
+        assert_eq!(session2.run(), echo(WAITING_LINES..20).as_bytes());
+        let args = unsafe { Box::from_raw(core1().to_core1.pop_front().unwrap() as *mut Args) };
+        assert_eq!(args.get(0), Some("2"));
+        let expected: Vec<_> = (0..20).map(|i| Some(format!("{i}"))).collect();
+        assert_eq!(core1().lines(), expected);
+        core1().output(None);
+        assert_eq!(session2.run(), b"\x1b[?2004l\r\n$ ");
+    }
+}
@@ -1,17 +1,55 @@
 extern crate std;
 
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
 use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, LazyLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Waker;
+use std::time::{Duration, Instant};
 use crate::aoc::AocRunner;
-use aoc_pico::shell::{Command, Commands, Console, InputParser, MutexQueue, RunningCommand, SyncCommand, SyncRunningCommand};
+use aoc_pico::shell::{Args, Command, Commands, Console, ConsoleConfig, InputParser, MutexQueue, Newline, Output, RunningCommand, SyncCommand, SyncRunningCommand};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 #[tokio::main]
 pub async fn main() {
     let aoc_runner = AocRunner::new();
     let mut commands = Commands::new();
-    commands.add("aoc", SpawnerCommand::new(aoc_runner));
+    commands.add("aoc", AocRunner::HELP, SpawnerCommand::new(aoc_runner));
     let queue = MutexQueue::new();
-    let mut console = Console::new(InputParser::new(queue.clone()), commands);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut listen = None;
+    match args.as_slice() {
+        [] => {}
+        [flag, addr] if flag == "--listen" => listen = Some(addr),
+        [flag, file] if flag == "--script" => {
+            let script = std::fs::read(file).unwrap_or_else(|err| {
+                eprintln!("{}: {}", file, err);
+                std::process::exit(2);
+            });
+            let config = ConsoleConfig { newline: Newline::Lf, ..ConsoleConfig::default() };
+            let console = Console::new(InputParser::new(queue), commands, config).with_clock(millis);
+            std::process::exit(run_script(console, &script).await.into());
+        }
+        _ => {
+            eprintln!("usage: aoc-pico [--script <file> | --listen <address>]");
+            std::process::exit(2);
+        }
+    }
+    let commands = Rc::new(commands);
+    let local = tokio::task::LocalSet::new();
+    if let Some(addr) = listen {
+        let listener = TcpListener::bind(addr).unwrap_or_else(|err| {
+            eprintln!("{}: {}", addr, err);
+            std::process::exit(2);
+        });
+        local.spawn_local(accept_clients(listener, commands.clone()));
+    }
+    let mut console = Console::new(InputParser::new(queue.clone()), commands, ConsoleConfig::default())
+        .with_clock(millis)
+        .with_timer(wake_at)
+        .with_size_probe();
     crossterm::terminal::enable_raw_mode().unwrap();
     std::panic::set_hook(Box::new(|_| {
         crossterm::terminal::disable_raw_mode().unwrap();
@@ -23,7 +61,8 @@ pub async fn main() {
         loop {
             if let Ok(len) = stdin.read(buffer.as_mut()).await {
                 let str = &buffer[..len];
-                if str.contains(&b'\x03') {
+                // Ctrl-C is handled by the console, Ctrl-\ quits
+                if str.contains(&b'\x1c') {
                     break;
                 }
                 queue.push(str.into());
@@ -37,11 +76,106 @@ pub async fn main() {
             stdout.write_all(&buf2[..]).await.unwrap();
         }
     };
+    local
+        .run_until(async {
+            tokio::select!(
+                _ = input_loop => (),
+                _ = output_loop => ()
+            )
+        })
+        .await;
+    crossterm::terminal::disable_raw_mode().unwrap();
+}
+
+/// Give each client a session of its own, sharing the commands of the terminal
+async fn accept_clients(listener: TcpListener, commands: Rc<Commands>) {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if sender.blocking_send(stream).is_err() {
+                break;
+            }
+        }
+    });
+    while let Some(stream) = receiver.recv().await {
+        tokio::task::spawn_local(serve_client(stream, commands.clone()));
+    }
+}
+
+/// Session of a client until it disconnects, its socket being read and written by threads of their own
+async fn serve_client(stream: TcpStream, commands: Rc<Commands>) {
+    let Ok(mut reader) = stream.try_clone() else {
+        return;
+    };
+    let mut writer = stream;
+    let (input_sender, mut input) = tokio::sync::mpsc::channel(4);
+    std::thread::spawn(move || {
+        let mut buffer = vec![0u8; 1024];
+        while let Ok(len @ 1..) = reader.read(&mut buffer) {
+            if input_sender.blocking_send(buffer[..len].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    let (output, output_receiver) = std::sync::mpsc::channel::<Vec<u8>>();
+    std::thread::spawn(move || {
+        for buf in output_receiver {
+            if writer.write_all(&buf).is_err() {
+                break;
+            }
+        }
+    });
+    let queue = MutexQueue::new();
+    let mut console = Console::new(InputParser::new(queue.clone()), commands, ConsoleConfig::default())
+        .with_clock(millis)
+        .with_timer(wake_at)
+        .with_size_probe();
+    let input_loop = async {
+        while let Some(bytes) = input.recv().await {
+            queue.push(bytes);
+        }
+    };
+    let output_loop = async {
+        loop {
+            let (buf1, buf2) = console.next_wait().await;
+            if output.send([buf1, buf2].concat()).is_err() {
+                break;
+            }
+        }
+    };
     tokio::select!(
         _ = input_loop => (),
         _ = output_loop => ()
     );
-    crossterm::terminal::disable_raw_mode().unwrap();
+}
+
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Milliseconds since the first call
+fn millis() -> u64 {
+    START.elapsed().as_millis() as u64
+}
+
+/// Wake the console at `deadline`, from a thread sleeping until then
+fn wake_at(deadline: u64, waker: &Waker) {
+    let waker = waker.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(deadline.saturating_sub(millis())));
+        waker.wake();
+    });
+}
+
+/// Run a script without a terminal, returning the exit status of its last command line
+async fn run_script(mut console: Console<InputParser<MutexQueue>>, script: &[u8]) -> u8 {
+    let mut stdout = tokio::io::stdout();
+    console.run_script(script);
+    while console.scripting() {
+        let (buf1, buf2) = console.next_wait().await;
+        stdout.write_all(&buf1[..]).await.unwrap();
+        stdout.write_all(&buf2[..]).await.unwrap();
+    }
+    stdout.flush().await.unwrap();
+    console.status()
 }
 
 struct SpawnerCommand<C: SyncCommand> {
@@ -54,30 +188,64 @@ impl<C: SyncCommand> SpawnerCommand<C> {
     }
 }
 
-impl<C: SyncCommand> Command for SpawnerCommand<C> {
-    fn exec(&self, args: Vec<String>, input: Vec<String>) -> Box<dyn RunningCommand> {
-        let inner = &self.inner;
+impl<C: SyncCommand + Clone + Send + 'static> Command for SpawnerCommand<C> {
+    fn exec(&self, args: Args) -> Box<dyn RunningCommand> {
+        let inner = self.inner.clone();
         let (sender, receiver) = tokio::sync::mpsc::channel(3);
-        let mut running = inner.exec_sync(args, input);
+        let (input, input_receiver) = std::sync::mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let thread_cancelled = cancelled.clone();
         tokio::task::spawn_blocking(move || {
-            while let Some(s) = running.next_sync() {
-                sender.blocking_send(s).unwrap();
+            // waits for each line until the input sender is dropped
+            let mut lines = input_receiver.into_iter();
+            let mut running = inner.exec_sync(args, &mut lines);
+            loop {
+                if thread_cancelled.load(Ordering::Relaxed) {
+                    running.cancel_sync();
+                    break;
+                }
+                let Some(s) = running.next_sync() else { break };
+                if sender.blocking_send(s).is_err() {
+                    break;
+                }
             }
         });
         Box::new(SpawnedCommand {
-            receiver
+            receiver,
+            input: Some(input),
+            cancelled,
         })
     }
+
+    fn complete(&self, args: &[&str], prefix: &str) -> Vec<String> {
+        self.inner.complete(args, prefix)
+    }
 }
 
 struct SpawnedCommand {
-    receiver: tokio::sync::mpsc::Receiver<String>,
+    receiver: tokio::sync::mpsc::Receiver<Output>,
+    // dropped at the end of input
+    input: Option<std::sync::mpsc::Sender<String>>,
+    cancelled: Arc<AtomicBool>,
 }
 
 impl RunningCommand for SpawnedCommand {
-    fn next(&mut self) -> Pin<Box<dyn Future<Output=Option<String>> + Send + '_>> {
+    fn next(&mut self) -> Pin<Box<dyn Future<Output=Option<Output>> + Send + '_>> {
         Box::pin(self.receiver.recv())
     }
+
+    fn input(&mut self, line: Option<String>) {
+        match (line, &self.input) {
+            // the command may have stopped reading its input
+            (Some(line), Some(input)) => { let _ = input.send(line); }
+            _ => self.input = None,
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.input = None;
+    }
 }
 
 pub fn debug_heap_size(_place: &str) {}
@@ -5,6 +5,9 @@ extern crate alloc;
 
 pub mod aoc;
 
+#[cfg(any(target_os = "none", test))]
+mod core1;
+
 #[cfg(target_os = "none")]
 mod pico;
 #[cfg(target_os = "none")]
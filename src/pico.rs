@@ -20,6 +20,7 @@ mod app;
 mod dma;
 pub(crate) mod memory;
 mod multicore;
+mod timer;
 
 #[unsafe(link_section = ".boot2")]
 #[unsafe(no_mangle)]
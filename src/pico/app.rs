@@ -1,9 +1,11 @@
 use crate::pico::{run_console, MutexInputQueue};
 use crate::pico::dma::TimeoutDmaReader;
-use crate::pico::memory::{init_heap, install_core0_stack_guard, read_sp};
+use crate::pico::memory::{heap_stats, init_heap, install_core0_stack_guard, read_sp};
 use crate::pico::multicore::create_multicore_runner;
+use crate::pico::timer::{init_timer, wake_at};
 use crate::aoc::AocRunner;
-use aoc_pico::shell::{Commands, Console, InputParser};
+use alloc::rc::Rc;
+use aoc_pico::shell::{Commands, Console, ConsoleConfig, InputParser};
 use core::pin::pin;
 use cortex_m::peripheral::NVIC;
 use cortex_m::singleton;
@@ -55,15 +57,37 @@ fn entry() -> ! {
     .unwrap()
     .split();
 
+    let (uart1_rx, uart1_tx) = UartPeripheral::new(
+        pac.UART1,
+        (pins.gpio4.into_function(), pins.gpio5.into_function()),
+        &mut pac.RESETS,
+    )
+    .enable(UartConfig::default(), clocks.peripheral_clock.freq())
+    .unwrap()
+    .split();
+
     let aoc_runner = AocRunner::new();
     let fifo = sio.fifo;
     let multicore_runner = create_multicore_runner(fifo, aoc_runner);
     debug!("multicore started");
     let mut commands = Commands::new();
-    commands.add("aoc", multicore_runner);
+    commands.add("aoc", AocRunner::HELP, multicore_runner);
+    // a session on each uart, `aoc` running on core1 for one of them at a time
+    let commands = Rc::new(commands);
+    let session = |input: &'static MutexInputQueue| {
+        Console::new(InputParser::new(input), commands.clone(), ConsoleConfig::default())
+            .with_heap_stats(heap_stats)
+            .with_clock(millis)
+            .with_timer(wake_at)
+            .with_core_id(|| Sio::core() as u8)
+            .with_size_probe()
+    };
 
+    init_timer(timer.alarm_2().unwrap());
     let console_input = singleton!(: MutexInputQueue = MutexInputQueue::new()).unwrap();
-    let console = Console::new(InputParser::new(&*console_input), commands);
+    let console = session(console_input);
+    let console1_input = singleton!(: MutexInputQueue = MutexInputQueue::new()).unwrap();
+    let console1 = session(console1_input);
 
     let mut dma_chans = pac.DMA.split(&mut pac.RESETS);
     dma_chans.ch0.enable_irq0();
@@ -76,11 +100,23 @@ fn entry() -> ! {
         |v| console_input.push(v),
     );
 
+    dma_chans.ch2.enable_irq0();
+    dma_chans.ch3.enable_irq1();
+    let double_dma1 = TimeoutDmaReader::<_, _, _, _, 512>::new(
+        dma_chans.ch3,
+        AsyncAlarm::new(timer.alarm_1().unwrap()),
+        uart1_rx,
+        |v| console1_input.push(v),
+    );
+
     unsafe {
         NVIC::unmask(Interrupt::DMA_IRQ_0);
         NVIC::unmask(Interrupt::DMA_IRQ_1);
         NVIC::unmask(Interrupt::UART0_IRQ);
+        NVIC::unmask(Interrupt::UART1_IRQ);
         NVIC::unmask(Interrupt::TIMER_IRQ_0);
+        NVIC::unmask(Interrupt::TIMER_IRQ_1);
+        NVIC::unmask(Interrupt::TIMER_IRQ_2);
     }
 
     debug!("stack pointer: {:x}", read_sp());
@@ -89,5 +125,20 @@ fn entry() -> ! {
     Wfi::run_loop([
         pin!(run_console(console, uart_tx, dma_chans.ch0)),
         pin!(double_dma.run()),
+        pin!(run_console(console1, uart1_tx, dma_chans.ch2)),
+        pin!(double_dma1.run()),
     ])
 }
+
+/// Milliseconds since boot, from the free-running timer
+fn millis() -> u64 {
+    let timer = unsafe { rp2040_hal::pac::Peripherals::steal().TIMER };
+    loop {
+        // the high word is read again in case the low one wrapped in between
+        let high = timer.timerawh().read().bits();
+        let low = timer.timerawl().read().bits();
+        if timer.timerawh().read().bits() == high {
+            return ((u64::from(high) << 32) | u64::from(low)) / 1000;
+        }
+    }
+}
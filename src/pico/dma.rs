@@ -10,8 +10,7 @@ use rp2040_hal::dma::single_buffer::{Config, Transfer};
 use rp2040_hal::dma::{
     Channel, ChannelIndex, EndlessReadTarget, ReadTarget, SingleChannel, WriteTarget,
 };
-use rp2040_hal::pac::UART0;
-use rp2040_hal::uart::{Reader, ValidUartPinout};
+use rp2040_hal::uart::{Reader, UartDevice, ValidUartPinout};
 
 struct VecCapWriteTarget(Vec<u8>);
 
@@ -62,8 +61,8 @@ impl<
         }
     }
 }
-impl<CH: ChannelIndex, ALARM: DelayNs, P: ValidUartPinout<UART0>, F: Fn(Vec<u8>), const N: usize>
-    TimeoutDmaReader<CH, ALARM, Reader<UART0, P>, F, N>
+impl<CH: ChannelIndex, ALARM: DelayNs, U: UartDevice, P: ValidUartPinout<U>, F: Fn(Vec<u8>), const N: usize>
+    TimeoutDmaReader<CH, ALARM, Reader<U, P>, F, N>
 {
     pub async fn run(self) {
         let Self {
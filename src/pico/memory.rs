@@ -1,3 +1,4 @@
+use aoc_pico::shell::HeapStats;
 use cortex_m_rt::heap_start;
 use crate::debug;
 use embedded_alloc::LlffHeap as Heap;
@@ -26,6 +27,13 @@ pub fn debug_heap_size(place: &str) {
     debug!("HEAP usage at {}: {}k ({}%)", place, used / 1024, percent);
 }
 
+pub(crate) fn heap_stats() -> HeapStats {
+    HeapStats {
+        used: HEAP.used(),
+        free: HEAP.free(),
+    }
+}
+
 #[inline(always)]
 fn install_stack_guard(stack_bottom: usize) {
     debug!("Installing stack guard at {:X}", stack_bottom);
@@ -1,11 +1,10 @@
+use crate::core1::{CANCELLED, Fifo, MulticoreProxy};
 use crate::pico::memory::install_core1_stack_guard;
 use alloc::boxed::Box;
 use alloc::string::String;
-use alloc::vec::Vec;
-use aoc_pico::shell::{Command, RunningCommand, SyncCommand};
+use aoc_pico::shell::{Args, Output, SyncCommand, SyncRunningCommand};
 use core::cell::UnsafeCell;
-use core::future::{Future, ready};
-use core::pin::{pin, Pin};
+use core::sync::atomic::Ordering;
 use cortex_m::singleton;
 use critical_section::Mutex;
 use crate::debug;
@@ -13,85 +12,81 @@ use rp2040_hal::Sio;
 use rp2040_hal::pac::Peripherals;
 use rp2040_hal::sio::SioFifo;
 
-pub struct MulticoreProxy {
-    pub fifo: *mut SioFifo,
-}
-
-unsafe impl Send for MulticoreProxy {}
-
-impl Command for MulticoreProxy {
-    fn exec(&self, args: Vec<String>, input: Vec<String>) -> Box<dyn RunningCommand> {
-        let boxed = Box::new((args, input));
-        let ptr = Box::into_raw(boxed);
-        unsafe {(&mut *self.fifo).write_blocking(ptr as u32)};
-        let fifo: &'static mut SioFifo = unsafe { &mut *self.fifo };
-        Box::new(MulticoreReceiver::new(fifo))
+impl Fifo for SioFifo {
+    fn read(&mut self) -> Option<usize> {
+        SioFifo::read(self).map(|addr| addr as usize)
     }
-}
 
-pub struct MulticoreReceiver {
-    fifo: &'static mut SioFifo,
-    finished: bool,
+    fn write_blocking(&mut self, addr: usize) {
+        SioFifo::write_blocking(self, addr as u32)
+    }
 }
 
-impl MulticoreReceiver {
-    fn new(fifo: &'static mut SioFifo) -> Self {
-        Self {
-            fifo,
-            finished: false,
-        }
-    }
+/// Input lines sent by core0 for the running command, until `None`
+struct FifoLines<'a> {
+    fifo: &'a mut SioFifo,
+    ended: bool,
 }
 
-impl RunningCommand for MulticoreReceiver {
-    fn next(&mut self) -> Pin<Box<dyn Future<Output = Option<String>> + Send + '_>> {
-        if self.finished {
-            return Box::pin(ready(None));
-        }
-        let addr = self.fifo.read_blocking() as *mut Option<String>; // TODO: async
-        let item = unsafe { Box::from_raw(addr) };
-        if item.is_none() {
-            self.finished = true;
+impl Iterator for FifoLines<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while !self.ended {
+            let addr = self.fifo.read_blocking() as *mut Option<String>;
+            let line = *unsafe { Box::from_raw(addr) };
+            self.ended = line.is_none();
+            // once cancelled, the lines still sent until the end of input are read, not parsed
+            if !CANCELLED.load(Ordering::Relaxed) {
+                return line;
+            }
         }
-        Box::pin(ready(*item))
+        None
     }
 }
 
-struct MulticoreRunner<C: Command> {
+struct MulticoreRunner<C: SyncCommand> {
     fifo: SioFifo,
     inner: C,
 }
 
-impl<C: Command + 'static> MulticoreRunner<C> {
+impl<C: SyncCommand + 'static> MulticoreRunner<C> {
     fn new(fifo: SioFifo, inner: C) -> Self {
         Self { fifo, inner }
     }
 
     fn run(mut self) -> ! {
-        use micro_async::{Busy, RunLoop};
-        Busy::run_loop(
-            [pin!(async move {
-                loop {
-                    let addr = self.fifo.read_blocking() as *mut (Vec<String>, Vec<String>);
-                    let line = unsafe { Box::from_raw(addr) };
-                    let (args, input) = *line;
-                    let mut running = self.inner.exec(args, input);
-                    while let Some(res) = running.next().await {
-                        let boxed = Box::new(Some(res));
-                        self.fifo.write_blocking(Box::into_raw(boxed) as u32);
-                    }
-                    let none = Box::new(Option::<String>::None);
-                    self.fifo.write_blocking(Box::into_raw(none) as u32);
+        loop {
+            let addr = self.fifo.read_blocking() as *mut Args;
+            let args = *unsafe { Box::from_raw(addr) };
+            let mut lines = FifoLines { fifo: &mut self.fifo, ended: false };
+            let mut running = self.inner.exec_sync(args, &mut lines);
+            for _ in lines {}
+            // a part of a day takes long, not started once cancelled
+            while !CANCELLED.load(Ordering::Relaxed) {
+                let Some(res) = running.next_sync() else {
+                    break;
+                };
+                if CANCELLED.load(Ordering::Relaxed) {
+                    break;
                 }
-            })]
-        )
+                let boxed = Box::new(Some(res));
+                self.fifo.write_blocking(Box::into_raw(boxed) as u32);
+            }
+            if CANCELLED.load(Ordering::Relaxed) {
+                running.cancel_sync();
+            }
+            let none = Box::new(Option::<Output>::None);
+            self.fifo.write_blocking(Box::into_raw(none) as u32);
+        }
     }
 }
 
-pub fn create_multicore_runner(
+pub fn create_multicore_runner<C: SyncCommand + Clone + Send + 'static>(
     fifo0: SioFifo,
-    runner: impl SyncCommand + Send + 'static,
-) -> MulticoreProxy {
+    runner: C,
+) -> MulticoreProxy<SioFifo, C> {
+    let completer = runner.clone();
     let f = move || {
         let fifo1 = unsafe { Sio::new(Peripherals::steal().SIO).fifo };
         let multicore_runner = MulticoreRunner::new(fifo1, runner);
@@ -100,7 +95,7 @@ pub fn create_multicore_runner(
     };
     let fifo0 = singleton!(: SioFifo = fifo0).unwrap();
     start_core1_with_fn(fifo0, f);
-    MulticoreProxy { fifo: fifo0 }
+    MulticoreProxy::new(fifo0, completer)
 }
 
 type MutexCell<T> = Mutex<UnsafeCell<T>>;
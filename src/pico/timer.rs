@@ -0,0 +1,58 @@
+//! Alarm 2 of the timer, waking the consoles waiting for a clock time
+//!
+//! Alarms 0 and 1 end the reads of the uarts. The consoles share this one, set for the earliest deadline:
+//! the ones waiting for a later one are woken early, and set it again.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::task::Waker;
+use critical_section::Mutex;
+use rp2040_hal::pac::interrupt;
+use rp2040_hal::timer::{Alarm, Alarm2, Instant};
+
+struct Wakeup {
+    alarm: Alarm2,
+    // clock time the alarm is set for, in milliseconds
+    deadline: Option<u64>,
+    wakers: Vec<Waker>,
+}
+
+static WAKEUP: Mutex<RefCell<Option<Wakeup>>> = Mutex::new(RefCell::new(None));
+
+pub(crate) fn init_timer(mut alarm: Alarm2) {
+    alarm.enable_interrupt();
+    critical_section::with(|cs| {
+        WAKEUP.replace(cs, Some(Wakeup { alarm, deadline: None, wakers: Vec::new() }));
+    });
+}
+
+/// Wake `waker` at `deadline`, in milliseconds since boot as the clock of the consoles
+pub(crate) fn wake_at(deadline: u64, waker: &Waker) {
+    critical_section::with(|cs| {
+        let mut wakeup = WAKEUP.borrow_ref_mut(cs);
+        let Some(Wakeup { alarm, deadline: set, wakers }) = wakeup.as_mut() else {
+            return waker.wake_by_ref();
+        };
+        if set.is_none_or(|set| deadline < set) {
+            // farther than the alarm counts, the console checking its deadline now instead
+            if alarm.schedule_at(Instant::from_ticks(deadline * 1000)).is_err() {
+                return waker.wake_by_ref();
+            }
+            *set = Some(deadline);
+        }
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    });
+}
+
+#[interrupt]
+fn TIMER_IRQ_2() {
+    critical_section::with(|cs| {
+        if let Some(Wakeup { alarm, deadline, wakers }) = WAKEUP.borrow_ref_mut(cs).as_mut() {
+            alarm.clear_interrupt();
+            *deadline = None;
+            wakers.drain(..).for_each(Waker::wake);
+        }
+    });
+}
@@ -1,11 +1,40 @@
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
-use alloc::collections::VecDeque;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::format;
+use alloc::rc::Rc;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use core::future::Future;
+use core::cell::Cell;
+use core::future::{Future, poll_fn};
 use core::pin::Pin;
-use core::future::ready;
+use core::future::pending;
+use core::task::{Poll, Waker};
+use args::{CommandLine, InputFrom, SimpleCommand};
+use buffers::Buffers;
+use config::Marker;
+pub use config::{ConsoleConfig, Newline, TerminalSize, Theme};
+use editor::{Edit, LineEditor};
+use history::History;
+use pager::Pager;
+use script::Script;
+
+mod args;
+mod buffers;
+mod config;
+#[cfg(target_os = "linux")]
+mod driver;
+mod editor;
+mod frame;
+mod history;
+mod pager;
+mod script;
+mod unicode;
+
+pub use args::Args;
+#[cfg(target_os = "linux")]
+pub use driver::{ConsoleDriver, Transcript};
+pub use frame::{HANDSHAKE, Request, Response, unframe};
 
 #[allow(dead_code)]
 #[derive(Eq, PartialEq, Debug)]
@@ -14,22 +43,158 @@ pub enum Input {
     IncompleteLine(String),
     Control(char),
     EscapeSequence(EscapeSequence),
+    /// Bytes that are not a valid frame; invalid UTF-8 is replaced by U+FFFD in the text instead
     InvalidByteSequence(Vec<u8>),
+    /// Message of the framed protocol, once COBS-decoded
+    Frame(Vec<u8>),
 }
 
 #[allow(dead_code)]
 #[derive(Eq, PartialEq, Debug)]
 pub enum EscapeSequence {
+    Key(Key, Modifiers),
+    CursorPosition { row: u16, col: u16 },
+    /// answer to `CSI 18 t`
+    TextAreaSize { rows: u16, cols: u16 },
+    PasteStart,
+    PasteEnd,
+    /// `HANDSHAKE` of the framed protocol
+    FramedMode,
     Unknown(Vec<u8>),
 }
 
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Key {
+    Up,
+    Down,
+    Right,
+    Left,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    F(u8),
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    /// xterm encodes modifiers as `1 + bitmask` in the second parameter
+    fn from_param(param: u16) -> Self {
+        let bits = param.saturating_sub(1);
+        Self {
+            shift: bits & 1 != 0,
+            alt: bits & 2 != 0,
+            ctrl: bits & 4 != 0,
+            meta: bits & 8 != 0,
+        }
+    }
+}
+
 impl From<Vec<u8>> for EscapeSequence {
     fn from(value: Vec<u8>) -> Self {
         //debug!("escape sequence: {=[u8]:X}", value);
-        EscapeSequence::Unknown(value)
+        EscapeSequence::decode(&value).unwrap_or(EscapeSequence::Unknown(value))
+    }
+}
+
+impl EscapeSequence {
+    fn decode(seq: &[u8]) -> Option<Self> {
+        match seq {
+            HANDSHAKE => Some(EscapeSequence::FramedMode),
+            // SS3: VT100 application mode
+            [b'\x1b', b'O', last] => {
+                let key = match last {
+                    b'A' => Key::Up,
+                    b'B' => Key::Down,
+                    b'C' => Key::Right,
+                    b'D' => Key::Left,
+                    b'H' => Key::Home,
+                    b'F' => Key::End,
+                    b'P'..=b'S' => Key::F(last - b'P' + 1),
+                    _ => return None,
+                };
+                Some(EscapeSequence::Key(key, Modifiers::default()))
+            }
+            // Linux console function keys
+            [b'\x1b', b'[', b'[', last @ b'A'..=b'E'] => {
+                Some(EscapeSequence::Key(Key::F(last - b'A' + 1), Modifiers::default()))
+            }
+            [b'\x1b', b'[', params @ .., last] => Self::decode_csi(params, *last),
+            _ => None,
+        }
+    }
+
+    fn decode_csi(params: &[u8], last: u8) -> Option<Self> {
+        let params = parse_params(params)?;
+        let param = |i: usize, default: u16| match params.get(i) {
+            Some(0) | None => default,
+            Some(&p) => p,
+        };
+        let key = match last {
+            b'A' => Key::Up,
+            b'B' => Key::Down,
+            b'C' => Key::Right,
+            b'D' => Key::Left,
+            b'H' => Key::Home,
+            b'F' => Key::End,
+            b'P' => Key::F(1),
+            b'Q' => Key::F(2),
+            b'S' => Key::F(4),
+            b'R' => {
+                return Some(EscapeSequence::CursorPosition {
+                    row: param(0, 1),
+                    col: param(1, 1),
+                });
+            }
+            b't' if param(0, 0) == 8 => {
+                return Some(EscapeSequence::TextAreaSize {
+                    rows: param(1, 1),
+                    cols: param(2, 1),
+                });
+            }
+            b'~' => match param(0, 0) {
+                1 | 7 => Key::Home,
+                2 => Key::Insert,
+                3 => Key::Delete,
+                4 | 8 => Key::End,
+                5 => Key::PageUp,
+                6 => Key::PageDown,
+                n @ 11..=15 => Key::F((n - 10) as u8),
+                n @ 17..=21 => Key::F((n - 11) as u8),
+                n @ 23..=24 => Key::F((n - 12) as u8),
+                200 => return Some(EscapeSequence::PasteStart),
+                201 => return Some(EscapeSequence::PasteEnd),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(EscapeSequence::Key(key, Modifiers::from_param(param(1, 1))))
     }
 }
 
+/// Parse `;`-separated numeric parameters, empty ones being 0.
+/// Private parameters and intermediate bytes are not supported.
+fn parse_params(params: &[u8]) -> Option<Vec<u16>> {
+    params
+        .split(|&b| b == b';')
+        .map(|p| {
+            p.iter().try_fold(0u16, |acc, &b| match b {
+                b'0'..=b'9' => Some(acc.saturating_mul(10).saturating_add((b - b'0') as u16)),
+                _ => None,
+            })
+        })
+        .collect()
+}
+
 pub trait InputQueue {
     fn pop(&mut self) -> Option<Vec<u8>>;
 }
@@ -49,11 +214,22 @@ pub trait AsyncInputQueue: InputQueue {
 pub struct InputParser<Q: InputQueue> {
     queue: Q,
     current: VecDeque<u8>,
+    // inside a bracketed paste, control bytes are part of the text
+    paste: bool,
+    // a `\n` right after `\r` does not end another line
+    after_cr: bool,
+    // after the handshake, input bytes are frames until a quit message
+    framed: bool,
+    // bytes of the frame being received
+    frame: Vec<u8>,
 }
 
 pub trait AsyncInputIterator {
     #[allow(async_fn_in_trait)]
     async fn next_wait(&mut self) -> Input;
+
+    /// Following inputs are frames, the console having accepted the handshake
+    fn start_frames(&mut self);
 }
 
 impl<Q: InputQueue> InputParser<Q> {
@@ -61,6 +237,10 @@ impl<Q: InputQueue> InputParser<Q> {
         Self {
             queue,
             current: VecDeque::new(),
+            paste: false,
+            after_cr: false,
+            framed: false,
+            frame: Vec::new(),
         }
     }
 
@@ -73,6 +253,21 @@ impl<Q: InputQueue> InputParser<Q> {
     }
 }
 
+/// Inputs from the bytes already received, `None` until more are pushed to the queue
+///
+/// No byte is lost when `next_wait` is dropped while waiting, the parser keeping what it received.
+impl<Q: AsyncInputQueue> Iterator for InputParser<Q> {
+    type Item = Input;
+
+    fn next(&mut self) -> Option<Input> {
+        let future = core::pin::pin!(self.next_wait());
+        match future.poll(&mut core::task::Context::from_waker(core::task::Waker::noop())) {
+            Poll::Ready(input) => Some(input),
+            Poll::Pending => None,
+        }
+    }
+}
+
 impl<Q: AsyncInputQueue> InputParser<Q> {
     async fn pop_byte_wait(&mut self) -> u8 {
         if let Some(byte) = self.pop_byte() {
@@ -81,15 +276,50 @@ impl<Q: AsyncInputQueue> InputParser<Q> {
         self.current = self.queue.pop_wait().await.into();
         Box::pin(self.pop_byte_wait()).await
     }
+
+    /// Next message, zero bytes delimiting the COBS-encoded frames
+    async fn next_frame(&mut self) -> Input {
+        loop {
+            match self.pop_byte_wait().await {
+                0 if self.frame.is_empty() => {}
+                0 => break,
+                b => self.frame.push(b),
+            }
+        }
+        let encoded = core::mem::take(&mut self.frame);
+        match unframe(&encoded) {
+            Some(message) => {
+                self.framed = !frame::is_quit(&message);
+                Input::Frame(message)
+            }
+            None => Input::InvalidByteSequence(encoded),
+        }
+    }
 }
 
 impl<Q: AsyncInputQueue> AsyncInputIterator for InputParser<Q> {
     async fn next_wait(&mut self) -> Input {
-        let mut acc = ParserAccumulator::new();
+        if self.framed {
+            return self.next_frame().await;
+        }
+        let mut acc = ParserAccumulator::new(self.paste);
         let mut b = self.pop_byte_wait().await;
+        if core::mem::take(&mut self.after_cr) && b == b'\n' {
+            return Box::pin(self.next_wait()).await;
+        }
         loop {
+            if acc.interrupts_line(b) {
+                self.current.push_front(b);
+                return Input::IncompleteLine(acc.into_text());
+            }
             match acc.advance(b) {
                 Ok(input) => {
+                    match input {
+                        Input::Line(_) => self.after_cr = b == b'\r',
+                        Input::EscapeSequence(EscapeSequence::PasteStart) => self.paste = true,
+                        Input::EscapeSequence(EscapeSequence::PasteEnd) => self.paste = false,
+                        _ => {}
+                    }
                     return input;
                 }
                 Err(acc2) => {
@@ -98,6 +328,13 @@ impl<Q: AsyncInputQueue> AsyncInputIterator for InputParser<Q> {
             }
             match self.pop_byte() {
                 Some(byte) => b = byte,
+                // only part of an escape sequence, parsed again with the next bytes
+                None if acc.current_line.is_empty() => {
+                    self.current = acc.state.into_bytes().into();
+                    let more = self.queue.pop_wait().await;
+                    self.current.extend(more);
+                    return Box::pin(self.next_wait()).await;
+                }
                 None => {
                     self.current = acc.state.into_bytes().into();
                     return Input::IncompleteLine(acc.current_line);
@@ -105,25 +342,65 @@ impl<Q: AsyncInputQueue> AsyncInputIterator for InputParser<Q> {
             }
         }
     }
+
+    fn start_frames(&mut self) {
+        self.framed = true;
+    }
 }
 
 struct ParserAccumulator {
     state: State,
     current_line: String,
+    paste: bool,
 }
 
 impl ParserAccumulator {
-    fn with(current_line: String, state: State) -> Self {
+    fn with(current_line: String, state: State, paste: bool) -> Self {
         Self {
             state,
             current_line,
+            paste,
         }
     }
 
-    fn new() -> Self {
+    fn new(paste: bool) -> Self {
         Self {
             state: State::Normal,
             current_line: String::with_capacity(64),
+            paste,
+        }
+    }
+
+    /// Control bytes and escape sequences are returned on their own, after the text typed before them
+    ///
+    /// Inside a bracketed paste, only an escape sequence (that may end the paste) is.
+    fn interrupts_line(&self, b: u8) -> bool {
+        let pending = match self.state {
+            State::Normal => !self.current_line.is_empty(),
+            State::InUtf8(_) => true,
+            State::InEscape(_) => false,
+        };
+        pending
+            && match self.paste {
+                true => b == b'\x1b',
+                false => matches!(b, b'\x00'..=b'\x09' | b'\x0b' | b'\x0c' | b'\x0e'..=b'\x1f' | b'\x7f'),
+            }
+    }
+
+    /// Text decoded so far, an unfinished UTF-8 sequence being replaced
+    fn into_text(mut self) -> String {
+        if let State::InUtf8(_) = self.state {
+            self.current_line.push(char::REPLACEMENT_CHARACTER);
+        }
+        self.current_line
+    }
+
+    /// Escape sequences other than the end of the paste are pasted text
+    fn escape(paste: bool, v: Vec<u8>) -> Input {
+        match EscapeSequence::from(v.clone()) {
+            EscapeSequence::PasteEnd => Input::EscapeSequence(EscapeSequence::PasteEnd),
+            _ if paste => Input::IncompleteLine(String::from_utf8_lossy(&v).into_owned()),
+            seq => Input::EscapeSequence(seq),
         }
     }
 
@@ -131,60 +408,95 @@ impl ParserAccumulator {
         let Self {
             state,
             mut current_line,
+            paste,
         } = self;
         match state {
             State::Normal => {
                 match b {
                     b'\n' | b'\r' => Ok(Input::Line(current_line)),
-                    b'\x1b' => Err(Self::with(current_line, State::InEscape(Vec::from([b])))),
+                    b'\x1b' => Err(Self::with(current_line, State::InEscape(Vec::from([b])), paste)),
+                    b'\x00'..=b'\x1f' | b'\x7f' if paste => {
+                        current_line.push(b as char);
+                        Err(Self::with(current_line, State::Normal, paste))
+                    }
                     b'\x00'..=b'\x1f' | b'\x7f' => {
                         //debug!("control: {:X}", b);
                         Ok(Input::Control(b as char))
                     }
                     b'\x20'..=b'\x7e' => {
                         current_line.push(b as char);
-                        Err(Self::with(current_line, State::Normal))
+                        Err(Self::with(current_line, State::Normal, paste))
+                    }
+                    b'\x80'..=b'\xff' if utf8_lead(b).is_some() => {
+                        Err(Self::with(current_line, State::InUtf8(Vec::from([b])), paste))
                     }
                     b'\x80'..=b'\xff' => {
-                        Err(Self::with(current_line, State::InUtf8(Vec::from([b]))))
+                        current_line.push(char::REPLACEMENT_CHARACTER);
+                        Err(Self::with(current_line, State::Normal, paste))
                     }
                 }
             }
             State::InUtf8(mut v) => {
+                let (len, second) = utf8_lead(v[0]).unwrap_or((1, 0x80..=0xbf));
+                let valid = match v.len() {
+                    1 => second.contains(&b),
+                    _ => matches!(b, b'\x80'..=b'\xbf'),
+                };
+                if !valid {
+                    // the bytes so far are replaced as one, and this one starts again
+                    current_line.push(char::REPLACEMENT_CHARACTER);
+                    return Self::with(current_line, State::Normal, paste).advance(b);
+                }
                 v.push(b);
-                if !matches!(b, b'\x80'..=b'\xff') {
-                    // invalid sequence
-                    Ok(Input::InvalidByteSequence(v))
-                } else if let Ok(s) = core::str::from_utf8(&v) {
-                    current_line.push_str(s);
-                    Err(Self::with(current_line, State::Normal))
-                } else {
-                    Err(Self::with(current_line, State::InUtf8(v)))
+                if v.len() < len {
+                    return Err(Self::with(current_line, State::InUtf8(v), paste));
                 }
+                current_line.push_str(core::str::from_utf8(&v).unwrap_or("\u{fffd}"));
+                Err(Self::with(current_line, State::Normal, paste))
             }
             State::InEscape(mut v) => {
                 v.push(b);
                 if v.len() == 2 {
-                    if b == b'[' {
-                        //debug!("CSI");
-                        Err(Self::with(current_line, State::InEscape(v)))
+                    if b == b'[' || b == b'O' {
+                        //debug!("CSI or SS3");
+                        Err(Self::with(current_line, State::InEscape(v), paste))
                     } else {
                         //debug!("1byte escape");
-                        Ok(Input::EscapeSequence(EscapeSequence::from(v)))
+                        Ok(Self::escape(paste, v))
                     }
+                } else if v[1] == b'O' {
+                    Ok(Self::escape(paste, v))
+                } else if v.len() == 3 && b == b'[' {
+                    //debug!("linux console function key");
+                    Err(Self::with(current_line, State::InEscape(v), paste))
                 } else if matches!(b, b'\x40'..=b'\x7e') {
                     //debug!("end of sequence");
                     // end of sequence
-                    Ok(Input::EscapeSequence(EscapeSequence::from(v)))
+                    Ok(Self::escape(paste, v))
                 } else {
                     //debug!("continue");
-                    Err(Self::with(current_line, State::InEscape(v)))
+                    Err(Self::with(current_line, State::InEscape(v), paste))
                 }
             }
         }
     }
 }
 
+/// Length of a UTF-8 sequence from its first byte, with the range of its second byte that keeps it
+/// from being overlong, a surrogate or over U+10FFFF
+fn utf8_lead(b: u8) -> Option<(usize, core::ops::RangeInclusive<u8>)> {
+    match b {
+        0xc2..=0xdf => Some((2, 0x80..=0xbf)),
+        0xe0 => Some((3, 0xa0..=0xbf)),
+        0xed => Some((3, 0x80..=0x9f)),
+        0xe1..=0xef => Some((3, 0x80..=0xbf)),
+        0xf0 => Some((4, 0x90..=0xbf)),
+        0xf1..=0xf3 => Some((4, 0x80..=0xbf)),
+        0xf4 => Some((4, 0x80..=0x8f)),
+        _ => None,
+    }
+}
+
 #[derive(Eq, PartialEq, Default)]
 enum State {
     #[default]
@@ -213,44 +525,254 @@ impl State {
     }
 }*/
 
+/// Item output by a running command
+#[derive(Eq, PartialEq, Debug)]
+pub enum Output {
+    Line(String),
+    /// shown distinctly, and not given to the next command of a pipeline
+    Error(String),
+    /// 0 for success, assumed when a command finishes without it
+    Exit(u8),
+}
+
+impl From<String> for Output {
+    fn from(line: String) -> Self {
+        Output::Line(line)
+    }
+}
+
 pub trait RunningCommand: Send {
-    fn next(&mut self) -> Pin<Box<dyn Future<Output = Option<String>> + Send + '_>>;
+    /// Next output item, `None` once finished
+    ///
+    /// The future may stay pending while the command waits for more input lines, it is polled
+    /// again after each call to `input`.
+    fn next(&mut self) -> Pin<Box<dyn Future<Output = Option<Output>> + Send + '_>>;
+
+    /// Input line received while the command runs, `None` at the end of input
+    fn input(&mut self, _line: Option<String>) {}
+
+    /// Whether the command takes another input line now, the console leaving the next ones unread until
+    /// it does, Ctrl-C included: the command then wakes the task polling `next`
+    fn wants_input(&self) -> bool {
+        true
+    }
+
+    /// Called on Ctrl-C, the command is dropped right after
+    fn cancel(&mut self) {}
 }
 
 pub trait Command {
-    fn exec(&self, args: Vec<String>, input: Vec<String>) -> Box<dyn RunningCommand>;
+    /// Start the command, its input lines are given afterward through `RunningCommand::input`
+    fn exec(&self, args: Args) -> Box<dyn RunningCommand>;
+
+    /// Candidates for the argument starting with `prefix`, `args` being the ones before it
+    fn complete(&self, _args: &[&str], _prefix: &str) -> Vec<String> {
+        Vec::new()
+    }
 }
 
+/// Input lines of a synchronous command, `next` waiting until a line is received
+pub type InputLines<'a> = &'a mut dyn Iterator<Item = String>;
+
 pub trait SyncCommand {
     type RunningCommand: SyncRunningCommand + 'static;
-    fn exec_sync(&self, args: Vec<String>, input: Vec<String>) -> Self::RunningCommand;
+    fn exec_sync(&self, args: Args, input: InputLines) -> Self::RunningCommand;
+
+    fn complete(&self, _args: &[&str], _prefix: &str) -> Vec<String> {
+        Vec::new()
+    }
 }
 
-impl<S: SyncCommand> Command for S {
-    fn exec(&self, args: Vec<String>, input: Vec<String>) -> Box<dyn RunningCommand> {
-        Box::new(self.exec_sync(args, input))
+/// Without a thread or a core of its own, a synchronous command cannot wait for its input:
+/// it is started once all its input lines are received, reading them from a queue that it empties.
+impl<S: SyncCommand + Clone + Send + 'static> Command for S {
+    fn exec(&self, args: Args) -> Box<dyn RunningCommand> {
+        Box::new(BufferedSync {
+            command: self.clone(),
+            args,
+            input: VecDeque::new(),
+            running: None,
+        })
+    }
+
+    fn complete(&self, args: &[&str], prefix: &str) -> Vec<String> {
+        SyncCommand::complete(self, args, prefix)
+    }
+}
+
+struct BufferedSync<S: SyncCommand> {
+    command: S,
+    args: Args,
+    input: VecDeque<String>,
+    running: Option<S::RunningCommand>,
+}
+
+/// Input lines of a synchronous command, each one dropped from the queue as the command reads it
+struct QueuedLines<'a>(&'a mut VecDeque<String>);
+
+impl Iterator for QueuedLines<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.0.pop_front()
+    }
+}
+
+impl<S: SyncCommand + Send> RunningCommand for BufferedSync<S> {
+    fn next(&mut self) -> Pin<Box<dyn Future<Output = Option<Output>> + Send + '_>> {
+        match &mut self.running {
+            Some(running) => Box::pin(poll_fn(|_| Poll::Ready(running.next_sync()))),
+            None => Box::pin(pending()),
+        }
+    }
+
+    fn input(&mut self, line: Option<String>) {
+        match line {
+            Some(line) => self.input.push_back(line),
+            None => {
+                let args = core::mem::take(&mut self.args);
+                let mut lines = QueuedLines(&mut self.input);
+                self.running = Some(self.command.exec_sync(args, &mut lines));
+                // the lines the command did not read
+                self.input = VecDeque::new();
+            }
+        }
+    }
+
+    fn cancel(&mut self) {
+        if let Some(running) = &mut self.running {
+            running.cancel_sync();
+        }
     }
 }
 
 pub trait SyncRunningCommand: Send {
-    fn next_sync(&mut self) -> Option<String>;
+    fn next_sync(&mut self) -> Option<Output>;
+
+    /// Stop as soon as possible, `next_sync` should return `None` afterward
+    fn cancel_sync(&mut self) {}
 }
 
 impl SyncRunningCommand for Box<dyn SyncRunningCommand> {
-    fn next_sync(&mut self) -> Option<String> {
+    fn next_sync(&mut self) -> Option<Output> {
         self.as_mut().next_sync()
     }
+
+    fn cancel_sync(&mut self) {
+        self.as_mut().cancel_sync()
+    }
 }
 
 impl<S: SyncRunningCommand> RunningCommand for S {
-    fn next(&mut self) -> Pin<Box<dyn Future<Output=Option<String>> + Send + '_>> {
-        Box::pin(ready(self.next_sync()))
+    fn next(&mut self) -> Pin<Box<dyn Future<Output=Option<Output>> + Send + '_>> {
+        // lazy, as the pipeline drops the futures of the commands not polled first
+        Box::pin(poll_fn(|_| Poll::Ready(self.next_sync())))
+    }
+
+    fn cancel(&mut self) {
+        self.cancel_sync()
+    }
+}
+
+/// Command output known in advance
+struct Lines(VecDeque<String>);
+
+impl From<Vec<String>> for Lines {
+    fn from(value: Vec<String>) -> Self {
+        Self(value.into())
+    }
+}
+
+impl SyncRunningCommand for Lines {
+    fn next_sync(&mut self) -> Option<Output> {
+        self.0.pop_front().map(Output::Line)
+    }
+}
+
+/// Builtin outputting its input lines as they are received, or some of them
+struct Filter {
+    kind: FilterKind,
+    // input lines not output yet
+    pending: VecDeque<String>,
+    ended: bool,
+}
+
+enum FilterKind {
+    Cat,
+    Grep(String),
+    Head(usize),
+}
+
+impl Filter {
+    fn new(kind: FilterKind) -> Self {
+        Self {
+            kind,
+            pending: VecDeque::new(),
+            ended: false,
+        }
+    }
+
+    fn poll_line(&mut self) -> Poll<Option<String>> {
+        loop {
+            if let FilterKind::Head(0) = self.kind {
+                return Poll::Ready(None);
+            }
+            let Some(line) = self.pending.pop_front() else {
+                return if self.ended { Poll::Ready(None) } else { Poll::Pending };
+            };
+            match &mut self.kind {
+                FilterKind::Cat => return Poll::Ready(Some(line)),
+                FilterKind::Grep(text) if line.contains(text.as_str()) => return Poll::Ready(Some(line)),
+                FilterKind::Grep(_) => {}
+                FilterKind::Head(count) => {
+                    *count -= 1;
+                    return Poll::Ready(Some(line));
+                }
+            }
+        }
     }
 }
 
+impl RunningCommand for Filter {
+    fn next(&mut self) -> Pin<Box<dyn Future<Output = Option<Output>> + Send + '_>> {
+        Box::pin(poll_fn(|_| self.poll_line().map(|line| line.map(Output::Line))))
+    }
+
+    fn input(&mut self, line: Option<String>) {
+        match line {
+            Some(line) => self.pending.push_back(line),
+            None => self.ended = true,
+        }
+    }
+}
+
+/// Description of a command shown by `help`
+#[derive(Clone, Copy)]
+pub struct CommandHelp {
+    pub summary: &'static str,
+    pub usage: &'static str,
+    /// argument and its description
+    pub args: &'static [(&'static str, &'static str)],
+}
+
+impl CommandHelp {
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::with_capacity(2 + self.args.len());
+        lines.push(format!("usage: {}", self.usage));
+        lines.push(self.summary.to_string());
+        let width = self.args.iter().map(|(arg, _)| arg.len()).max().unwrap_or_default();
+        for (arg, description) in self.args {
+            lines.push(format!("  {:<width$}  {}", arg, description));
+        }
+        lines
+    }
+}
+
+/// Registry of commands, shared by all the sessions of a device
 #[derive(Default)]
 pub struct Commands {
     names: Vec<&'static str>,
+    helps: Vec<CommandHelp>,
     commands: Vec<Box<dyn Command>>,
 }
 
@@ -259,233 +781,1701 @@ impl Commands {
         Default::default()
     }
 
-    pub fn add(&mut self, name: &'static str, command: impl Command + Send + 'static) {
+    pub fn add(&mut self, name: &'static str, help: CommandHelp, command: impl Command + Send + 'static) {
         self.names.push(name);
+        self.helps.push(help);
         self.commands.push(Box::new(command));
     }
 
-    fn get(&mut self, name: &str) -> Option<&mut Box<dyn Command>> {
-        let idx = self
-            .names
-            .iter()
-            .enumerate()
-            .find_map(|(i, &n)| (n == name).then_some(i))?;
-        Some(&mut self.commands[idx])
+    fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|&n| n == name)
+    }
+
+    fn get(&self, name: &str) -> Option<&dyn Command> {
+        let idx = self.index(name)?;
+        Some(self.commands[idx].as_ref())
+    }
+
+    fn help(&self, name: &str) -> Option<&CommandHelp> {
+        let idx = self.index(name)?;
+        Some(&self.helps[idx])
     }
 }
 
 pub struct Console<I> {
     input: I,
-    commands: Commands,
+    // shared by the sessions of a device, each with its own input and state
+    commands: Rc<Commands>,
+    buffers: Buffers,
+    heap_stats: Option<fn() -> HeapStats>,
+    history: History,
+    last_tab: bool,
+    // typed while a command runs, below its output, and given to the next prompt
+    type_ahead: Option<LineEditor>,
+    config: ConsoleConfig,
+    // exit status of the last command line, `$?`
+    status: u8,
+    clock: Option<fn() -> u64>,
+    // wakes the console at a clock time
+    timer: Option<fn(u64, &Waker)>,
+    // milliseconds taken by the last command line, `$DURATION`
+    duration: Option<u64>,
+    core_id: Option<fn() -> u8>,
+    // set with `set NAME=value`
+    variables: BTreeMap<String, String>,
+    // command lines run instead of the ones typed
+    script: Option<Script>,
+    // rest of a paste dropped, after the input it was for went over the limit
+    skip_paste: bool,
+    // started with `&`, polled at the prompt and along with the command line running
+    jobs: Vec<Job>,
+    // reported by the terminal, the configured one being assumed otherwise
+    size: Option<TerminalSize>,
+    // the terminal is asked for its size at the next prompt
+    probe: bool,
     state: ConsoleState,
 }
 
 enum ConsoleState {
-    Prompt(String),
-    ParsingInput {
-        cmd_line: String,
-        input: Vec<String>,
-        current_line: String,
-    },
-    RunCommand {
-        cmd_line: String,
-        input: Vec<String>,
-    },
-    RunningCommand(Box<dyn RunningCommand>),
-    Error(&'static str),
+    Prompt(LineEditor),
+    RunningCommand(Pipeline),
+    Error(Cow<'static, str>),
+    /// waiting for a request of the framed protocol
+    Framed,
     Poisoned,
 }
 
-impl Default for ConsoleState {
-    fn default() -> Self {
-        Self::Prompt(String::with_capacity(COLS))
-    }
+/// Commands of a command line, all started at once, each one receiving the output of the previous one
+struct Pipeline {
+    // `None` once finished
+    stages: Vec<Option<Box<dyn RunningCommand>>>,
+    // buffer receiving the output of the last command
+    output: Option<String>,
+    collected: Vec<String>,
+    // input of the first command being typed or pasted
+    input: Option<ConsoleInput>,
+    // exit status of the last command
+    status: u8,
+    framing: Framing,
+    // time from the console clock
+    started: Option<u64>,
+    // set for `wait`, which ends once these jobs are done
+    waiting: Option<Waiting>,
+    // set when the command line typed ends with `more`
+    pager: Option<Box<Pager>>,
 }
 
-impl<I> Console<I> {
-    pub fn new(input: I, commands: Commands) -> Self {
+/// How the output of a pipeline is shown
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Framing {
+    /// for someone at a terminal
+    Terminal,
+    /// plain lines of a script
+    Script,
+    /// messages of the framed protocol
+    Frames,
+}
+
+/// Command line started with `&`, its output lines going to a buffer as they come
+struct Job {
+    id: usize,
+    // as typed, without the `&`
+    line: String,
+    // its output is the `> name` buffer, or `%<id>`
+    pipeline: Pipeline,
+}
+
+/// Jobs `wait` waits for
+enum Waiting {
+    All,
+    Job(usize),
+}
+
+/// What a job did that is shown, above the line being typed
+enum JobEvent {
+    Nothing,
+    Error(String),
+    Done { id: usize, status: u8, notice: String },
+}
+
+/// Next output of any job, with the index of the job and of its command, never ready without jobs
+fn next_job(jobs: &mut [Job]) -> impl Future<Output = (usize, usize, Option<Output>)> + '_ {
+    let mut outputs: Vec<_> = jobs
+        .iter_mut()
+        .enumerate()
+        .map(|(j, job)| (j, Box::pin(job.pipeline.next())))
+        .collect();
+    poll_fn(move |cx| {
+        for (j, output) in outputs.iter_mut() {
+            if let Poll::Ready((idx, line)) = output.as_mut().poll(cx) {
+                return Poll::Ready((*j, idx, line));
+            }
+        }
+        Poll::Pending
+    })
+}
+
+struct ConsoleInput {
+    // heredoc terminator, Ctrl-D ends input otherwise
+    terminator: Option<String>,
+    line: LineEditor,
+    // bytes of the lines given so far
+    bytes: usize,
+    // inside a bracketed paste
+    pasting: bool,
+}
+
+impl ConsoleInput {
+    fn new(terminator: Option<String>, line: LineEditor) -> Self {
         Self {
-            input,
-            commands,
-            state: Default::default(),
+            terminator,
+            line,
+            bytes: 0,
+            pasting: false,
         }
     }
 }
 
-const EOL_NONE : &[u8] = b"";
-const EOL_PROMPT : &[u8] = b"\r\n$ ";
-const EOL_INPUT : &[u8] = b"\r\n< ";
-const EOL_RUN : &[u8] = b"\r\n> ";
+impl Pipeline {
+    /// Next output item of any running command, with its index
+    ///
+    /// The last commands are polled first so that lines do not pile up between commands.
+    fn next(&mut self) -> impl Future<Output = (usize, Option<Output>)> + '_ {
+        let mut outputs: Vec<_> = self
+            .stages
+            .iter_mut()
+            .enumerate()
+            .rev()
+            .filter_map(|(idx, stage)| Some((idx, stage.as_mut()?.next())))
+            .collect();
+        poll_fn(move |cx| {
+            for (idx, output) in outputs.iter_mut() {
+                if let Poll::Ready(line) = output.as_mut().poll(cx) {
+                    return Poll::Ready((*idx, line));
+                }
+            }
+            Poll::Pending
+        })
+    }
 
-const COLS : usize = 128;
-const COLS_SHRINK : usize = 32;
-const ROWS : usize = 256;
+    /// Whether input read from the console waits for the first command to take more
+    fn holds_input(&self) -> bool {
+        self.input.is_some() && self.stages.first().and_then(Option::as_ref).is_some_and(|first| !first.wants_input())
+    }
 
-impl<I: AsyncInputIterator> Console<I> {
-    pub async fn next_wait(&mut self) -> (Cow<'_, [u8]>, Cow<'_, [u8]>) {
-        match core::mem::replace(&mut self.state, ConsoleState::Poisoned) {
-            ConsoleState::RunCommand { cmd_line, input } => {
-                let mut args_iter = cmd_line.trim().split(' ').map(str::trim);
-                let name = args_iter.next().unwrap();
-                if let Some(command) = self.commands.get(name) {
-                    let args = args_iter.map(ToString::to_string).collect();
-                    self.state =
-                        ConsoleState::RunningCommand(command.exec(args, input));
-                } else {
-                    self.state = ConsoleState::Error("unknown command")
-                }
-                Box::pin(self.next_wait()).await
+    /// Give an input line to the first command, `None` ending its input
+    fn feed(&mut self, line: Option<String>) {
+        if line.is_none() {
+            self.input = None;
+        }
+        if let Some(Some(first)) = self.stages.first_mut() {
+            first.input(line);
+        }
+    }
+
+    /// Give all the input lines to the first command, for input read from the console
+    fn feed_all(&mut self, lines: Vec<String>) {
+        if self.input.take().is_some() {
+            for line in lines {
+                self.feed(Some(line));
             }
-            ConsoleState::RunningCommand(mut command) => {
-                if let Some(line) = command.next().await {
-                    self.state = ConsoleState::RunningCommand(command);
-                    return (b"\r\n> ".into(), line.into_bytes().into());
-                }
-                self.state = ConsoleState::Prompt(String::with_capacity(COLS));
-                (EOL_PROMPT.into(), EOL_NONE.into())
+            self.feed(None);
+        }
+    }
+
+    /// Stop the commands before `idx`, as nothing reads their output anymore
+    fn cancel_before(&mut self, idx: usize) {
+        for stage in &mut self.stages[..idx] {
+            if let Some(mut command) = stage.take() {
+                command.cancel();
             }
-            ConsoleState::Error(err) => {
-                let res = err.to_string();
-                self.state = ConsoleState::Prompt(String::with_capacity(COLS));
-                (res.into_bytes().into(), EOL_PROMPT.into())
-            }
-            ConsoleState::ParsingInput {
-                cmd_line,
-                input: mut input_lines,
-                mut current_line,
-                ..
-            } => match self.input.next_wait().await {
-                Input::Line(mut s) => {
-                    let start = if current_line.is_empty() {
-                        if s.len() < COLS_SHRINK {
-                            s.shrink_to_fit();
-                        }
-                        input_lines.push(s);
-                        0
-                    } else {
-                        let start = current_line.len();
-                        current_line.push_str(&s);
-                        if current_line.len() < COLS_SHRINK {
-                            current_line.shrink_to_fit();
-                        }
-                        input_lines.push(core::mem::replace(&mut current_line, String::with_capacity(COLS)));
-                        start
-                    };
-                    self.state = ConsoleState::ParsingInput {cmd_line, input: input_lines, current_line};
-                    let ConsoleState::ParsingInput {input, .. } = &self.state else { unreachable!() };
-                    (input.last().unwrap().as_bytes()[start..].into(), EOL_INPUT.into())
-                }
-                Input::IncompleteLine(s) => {
-                    let start = if current_line.is_empty() {
-                        current_line = s;
-                        0
-                    }  else {
-                        let start = current_line.len();
-                        current_line.push_str(&s);
-                        start
-                    };
-                    self.state = ConsoleState::ParsingInput {cmd_line, input: input_lines, current_line};
-                    let ConsoleState::ParsingInput {current_line, .. } = &self.state else { unreachable!() };
-                    (current_line.as_bytes()[start..].into(), EOL_NONE.into())
-                }
-                Input::Control('\x04') => {
-                    input_lines.push(current_line);
-                    self.state = ConsoleState::RunCommand {
-                        cmd_line,
-                        input: input_lines,
-                    };
-                    (EOL_RUN.into(), EOL_NONE.into())
-                }
-                _ => {
-                    self.state = ConsoleState::ParsingInput {cmd_line, input: input_lines, current_line};
-                    (EOL_NONE.into(), EOL_NONE.into())
-                }
-            },
-            ConsoleState::Prompt(mut prompt) => match self.input.next_wait().await {
-                Input::Line(s) => {
-                    prompt.push_str(&s);
-                    let eol = if let Some(prompt) = prompt.strip_suffix('<') {
-                        self.state = ConsoleState::ParsingInput {
-                            cmd_line: prompt.to_string(),
-                            input: Vec::with_capacity(ROWS),
-                            current_line: String::with_capacity(COLS),
-                        };
-                        EOL_INPUT
-                    } else {
-                        self.state = ConsoleState::RunCommand {
-                            cmd_line: prompt,
-                            input: Vec::new(),
-                        };
-                        EOL_RUN
-                    };
-                    (s.into_bytes().into(), eol.into())
-                }
-                Input::IncompleteLine(s) => {
-                    prompt.push_str(&s);
-                    self.state = ConsoleState::Prompt(prompt);
-                    (s.into_bytes().into(), EOL_NONE.into())
-                }
-                _ => {
-                    self.state = ConsoleState::Prompt(prompt);
-                    (EOL_NONE.into(), EOL_NONE.into())
-                },
-            },
-            ConsoleState::Poisoned => unreachable!(),
+        }
+        if self.stages[0].is_none() {
+            self.input = None;
         }
     }
 }
 
-#[cfg(target_os = "linux")]
-mod linux {
-    extern crate std;
-    use alloc::rc::Rc;
-    use std::collections::VecDeque;
-    use std::future::poll_fn;
-    use std::prelude::rust_2015::Vec;
-    use std::sync::Mutex;
-    use std::task::Poll;
-    use super::{AsyncInputQueue, InputQueue};
+/// Heap usage, shown by `ls` next to buffer sizes
+#[derive(Clone, Copy)]
+pub struct HeapStats {
+    pub used: usize,
+    pub free: usize,
+}
 
-    #[derive(Clone)]
-    pub struct MutexQueue(Rc<Mutex<(VecDeque<Vec<u8>>, Option<std::task::Waker>)>>);
-    impl MutexQueue {
-        pub fn new() -> Self {
-            Self(Rc::new(Mutex::new((VecDeque::new(), None))))
+impl<I> Console<I> {
+    /// Session reading `input`, its commands being shared with other sessions when given as an `Rc`
+    pub fn new(input: I, commands: impl Into<Rc<Commands>>, config: ConsoleConfig) -> Self {
+        Self {
+            input,
+            commands: commands.into(),
+            buffers: Buffers::default(),
+            heap_stats: None,
+            history: History::new(config.history_limit),
+            last_tab: false,
+            type_ahead: None,
+            status: 0,
+            clock: None,
+            timer: None,
+            duration: None,
+            core_id: None,
+            variables: BTreeMap::new(),
+            script: None,
+            skip_paste: false,
+            jobs: Vec::new(),
+            size: None,
+            probe: false,
+            state: ConsoleState::Prompt(LineEditor::new(unicode::width(&config.prompt), config.size.cols.into())),
+            config,
         }
+    }
 
-        pub fn push(&self, input: Vec<u8>) {
-            let mut inner = self.0.lock().unwrap();
-            inner.0.push_back(input.into());
-            inner.1.take().map(|waker| waker.wake());
+    /// Complete the word before the cursor, listing candidates on a second tab
+    fn tab_complete(&self, prompt: &mut LineEditor, list: bool) -> Vec<u8> {
+        let line = prompt.before_cursor();
+        let typed = line.len() - line.rfind(' ').map(|i| i + 1).unwrap_or(0);
+        let candidates = self.complete(line);
+        match candidates.as_slice() {
+            [] => b"\x07".to_vec(),
+            [single] => {
+                let mut completed = single[typed..].to_string();
+                completed.push(' ');
+                prompt.insert(&completed)
+            }
+            _ => {
+                let common = common_prefix(&candidates);
+                if common.len() > typed {
+                    let completed = common[typed..].to_string();
+                    prompt.insert(&completed)
+                } else if list {
+                    let mut echo = prompt.to_last_row();
+                    echo.extend_from_slice(self.config.newline().as_bytes());
+                    echo.extend_from_slice(candidates.join("  ").as_bytes());
+                    echo.extend(self.config.marker(Marker::Prompt));
+                    echo.extend(prompt.redisplay());
+                    echo
+                } else {
+                    b"\x07".to_vec()
+                }
+            }
         }
     }
-    impl InputQueue for MutexQueue {
-        fn pop(&mut self) -> Option<Vec<u8>> {
-            self.0.lock().unwrap().0.pop_front()
-        }
+
+    /// Run the command lines of a byte stream, stopping at the first one failing
+    ///
+    /// Nothing is echoed: each command line is output after the prompt, followed by the output
+    /// lines of its commands and by its exit status after `? `, each on a line of its own.
+    /// Commands reading their input from the console read the next lines of the script instead.
+    pub fn run_script(&mut self, script: &[u8]) {
+        self.script = Some(Script::from_bytes(script));
     }
-    impl AsyncInputQueue for MutexQueue {
-        async fn pop_wait(&mut self) -> Vec<u8> {
-            poll_fn(|ctx| {
-                match self.pop() {
-                    Some(buf) => Poll::Ready(buf),
-                    None => {
-                        self.0.lock().unwrap().1 = Some(ctx.waker().clone());
-                        Poll::Pending
-                    },
-                }
-            }).await
-        }
+
+    /// Whether a script is still running
+    pub fn scripting(&self) -> bool {
+        self.script.is_some()
     }
-}
-#[cfg(target_os = "linux")]
-pub use linux::*;
 
-#[cfg(all(target_os = "linux", test))]
-mod tests {
-    use super::*;
+    /// Exit status of the last command line
+    pub fn status(&self) -> u8 {
+        self.status
+    }
 
-    #[test]
+    pub fn with_heap_stats(mut self, heap_stats: fn() -> HeapStats) -> Self {
+        self.heap_stats = Some(heap_stats);
+        self
+    }
+
+    /// Error when `line` does not fit after `bytes` of input, in the configured limit or in the free heap
+    fn check_input(&self, bytes: usize, line: &str) -> Result<(), String> {
+        if let Some(limit) = self.config.input_limit
+            && bytes + line.len() > limit
+        {
+            return Err(format!("input over {} bytes, command stopped", limit));
+        }
+        if let Some(heap_stats) = self.heap_stats
+            && heap_stats().free < line.len() + HEAP_RESERVE
+        {
+            return Err("out of memory for input, command stopped".into());
+        }
+        Ok(())
+    }
+
+    /// Milliseconds from any fixed point, for `$DURATION`
+    pub fn with_clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Wake the task polling the console at a clock time, for the pager waiting for the size of the terminal
+    pub fn with_timer(mut self, timer: fn(u64, &Waker)) -> Self {
+        self.timer = Some(timer);
+        self
+    }
+
+    /// Core running the console, for `$CORE`
+    pub fn with_core_id(mut self, core_id: fn() -> u8) -> Self {
+        self.core_id = Some(core_id);
+        self
+    }
+
+    /// Ask the terminal for its size when started, as `resize` does
+    pub fn with_size_probe(mut self) -> Self {
+        self.probe = true;
+        self
+    }
+
+    /// Size of the terminal, as reported or configured
+    pub fn size(&self) -> TerminalSize {
+        self.size.unwrap_or(self.config.size)
+    }
+
+    /// Line typed after the prompt of `marker`, wrapping at the width of the terminal
+    fn editor(&self, marker: Marker) -> LineEditor {
+        LineEditor::new(unicode::width(self.config.prompt_of(marker)), self.size().cols.into())
+    }
+
+    fn ls(&self) -> Vec<String> {
+        let mut lines = self.buffers.lines();
+        if let Some(heap_stats) = self.heap_stats {
+            let HeapStats { used, free } = heap_stats();
+            lines.push(format!("heap: {} bytes used, {} bytes free", used, free));
+        }
+        lines
+    }
+
+    /// Start the commands of a command line, giving its input to the first one unless typed in the console
+    ///
+    /// A command line ending with `&` is started as a job, the pipeline returned only showing its number.
+    fn start(&mut self, mut command: CommandLine, line: &str) -> Result<Pipeline, Cow<'static, str>> {
+        if core::mem::take(&mut command.background) {
+            return self.start_job(command, line);
+        }
+        if let [SimpleCommand { name, args }] = command.pipeline.as_slice()
+            && matches!(name.as_str(), "fg" | "wait")
+        {
+            return Ok(self.resume(name == "fg", args.get(0))?);
+        }
+        let CommandLine { pipeline, input, mut output, .. } = command;
+        if pipeline.iter().rev().skip(1).any(|c| c.name == "more") {
+            return Err("more ends a command line".into());
+        }
+        let buffered = match &input {
+            InputFrom::Buffer(name) => Some(self.buffers.get(name).ok_or("no such buffer")?.clone()),
+            _ => None,
+        };
+        if let Some(SimpleCommand { args, .. }) = pipeline.last().filter(|c| c.name == "load") {
+            output = Some(args.get(0).ok_or("usage: load <name> <")?.into());
+        }
+        let mut stages = Vec::with_capacity(pipeline.len());
+        for command in pipeline {
+            match self.exec(command) {
+                Ok(command) => stages.push(Some(command)),
+                Err(err) => {
+                    stages.iter_mut().flatten().for_each(|command| command.cancel());
+                    return Err(err);
+                }
+            }
+        }
+        let mut pipeline = self.pipeline(stages, output);
+        match input {
+            InputFrom::Nothing => pipeline.feed(None),
+            InputFrom::Buffer(_) => {
+                for line in buffered.unwrap_or_default() {
+                    pipeline.feed(Some(line));
+                }
+                pipeline.feed(None);
+            }
+            InputFrom::Console => {
+                pipeline.input = Some(ConsoleInput::new(None, self.editor(Marker::Input)));
+            }
+            InputFrom::Heredoc(terminator) => {
+                pipeline.input = Some(ConsoleInput::new(Some(terminator), self.editor(Marker::Input)));
+            }
+        }
+        Ok(pipeline)
+    }
+
+    fn pipeline(&self, stages: Vec<Option<Box<dyn RunningCommand>>>, output: Option<String>) -> Pipeline {
+        Pipeline {
+            stages,
+            output,
+            collected: Vec::new(),
+            input: None,
+            status: 0,
+            framing: Framing::Terminal,
+            started: self.clock.map(|clock| clock()),
+            waiting: None,
+            pager: None,
+        }
+    }
+
+    /// Start a command line as a job, the pipeline returned showing its number
+    fn start_job(&mut self, command: CommandLine, line: &str) -> Result<Pipeline, Cow<'static, str>> {
+        let paged = command.pipeline.last().is_some_and(|c| c.name == "more");
+        if paged || matches!(command.input, InputFrom::Console | InputFrom::Heredoc(_)) {
+            return Err("a job cannot read the console".into());
+        }
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or_default() + 1;
+        let mut pipeline = self.start(command, line)?;
+        let buffer = pipeline.output.get_or_insert_with(|| format!("%{}", id));
+        self.buffers.insert(buffer.clone(), Vec::new());
+        let line = line.trim_end().trim_end_matches('&').trim_end().into();
+        self.jobs.push(Job { id, line, pipeline });
+        let notice: Box<dyn RunningCommand> = Box::new(Lines::from(Vec::from([format!("[{}]", id)])));
+        Ok(self.pipeline(Vec::from([Some(notice)]), None))
+    }
+
+    /// `fg` moves a job to the foreground, `wait` waits for one or for all of them
+    fn resume(&mut self, fg: bool, spec: Option<&str>) -> Result<Pipeline, &'static str> {
+        let waiting = match (fg, spec) {
+            (false, None) => Waiting::All,
+            (false, Some(_)) => Waiting::Job(self.jobs[self.job_index(spec)?].id),
+            (true, _) => {
+                let idx = self.job_index(spec)?;
+                let mut pipeline = self.jobs.remove(idx).pipeline;
+                // the rest of its output is shown, what came before stays in its buffer
+                pipeline.output = None;
+                pipeline.started = self.clock.map(|clock| clock());
+                return Ok(pipeline);
+            }
+        };
+        // only ends with its input, given once the jobs are done
+        let wait: Box<dyn RunningCommand> = Box::new(Filter::new(FilterKind::Cat));
+        let mut pipeline = self.pipeline(Vec::from([Some(wait)]), None);
+        pipeline.waiting = Some(waiting);
+        if self.jobs.is_empty() {
+            pipeline.feed(None);
+        }
+        Ok(pipeline)
+    }
+
+    /// Index of the job `%n`, the last one started by default
+    fn job_index(&self, spec: Option<&str>) -> Result<usize, &'static str> {
+        let Some(spec) = spec else {
+            return self.jobs.len().checked_sub(1).ok_or("no job");
+        };
+        let id = spec.strip_prefix('%').unwrap_or(spec).parse::<usize>();
+        self.jobs.iter().position(|job| Ok(job.id) == id).ok_or("no such job")
+    }
+
+    /// Give an output of a job to its next command or to its buffer
+    fn job_event(&mut self, j: usize, idx: usize, output: Option<Output>) -> JobEvent {
+        let job = &mut self.jobs[j];
+        let last = job.pipeline.stages.len() - 1;
+        match output {
+            Some(Output::Exit(status)) => {
+                if idx == last {
+                    job.pipeline.status = status;
+                }
+            }
+            Some(Output::Line(line)) if idx < last => {
+                if let Some(command) = &mut job.pipeline.stages[idx + 1] {
+                    command.input(Some(line));
+                }
+            }
+            Some(Output::Line(line)) => {
+                if let Some(buffer) = &job.pipeline.output {
+                    self.buffers.push(buffer, line);
+                }
+            }
+            Some(Output::Error(err)) => return JobEvent::Error(format!("[{}] {}", job.id, err)),
+            None if idx < last => {
+                job.pipeline.stages[idx] = None;
+                job.pipeline.cancel_before(idx);
+                if let Some(command) = &mut job.pipeline.stages[idx + 1] {
+                    command.input(None);
+                }
+            }
+            None => {
+                let mut job = self.jobs.remove(j);
+                job.pipeline.cancel_before(last);
+                let status = job.pipeline.status;
+                let state = match status {
+                    0 => "done".into(),
+                    status => format!("exit {}", status),
+                };
+                let notice = format!("[{}] {}  {}", job.id, state, job.line);
+                return JobEvent::Done { id: job.id, status, notice };
+            }
+        }
+        JobEvent::Nothing
+    }
+
+    /// Start a builtin or a registered command
+    fn exec(&mut self, command: SimpleCommand) -> Result<Box<dyn RunningCommand>, Cow<'static, str>> {
+        let SimpleCommand { name, mut args } = command;
+        let lines = match name.as_str() {
+            "help" => self.help(args.get(0))?,
+            "history" => self.history.lines(),
+            "echo" => [args.positional.join(" ")].into(),
+            "grep" => {
+                let text = args.get(0).ok_or("usage: grep <text>")?;
+                return Ok(Box::new(Filter::new(FilterKind::Grep(text.into()))));
+            }
+            "head" => {
+                let count = args.option("n").unwrap_or("10").parse().map_err(|_| "bad line count")?;
+                return Ok(Box::new(Filter::new(FilterKind::Head(count))));
+            }
+            // `load` lines are stored by the pipeline, as with `> name`, and `more` ones are paged by the console
+            "cat" | "load" | "more" => return Ok(Box::new(Filter::new(FilterKind::Cat))),
+            "ls" => self.ls(),
+            "source" => {
+                let name = args.get(0).ok_or("usage: source @<name>")?;
+                let lines = self.buffers.get(name.strip_prefix('@').unwrap_or(name)).ok_or("no such buffer")?;
+                self.script = Some(Script::new(lines.clone(), true));
+                Vec::new()
+            }
+            "set" => match (args.get(0), args.get(1)) {
+                (None, _) => {
+                    let mut lines = self.config.lines();
+                    lines.extend(self.variables.iter().map(|(name, value)| format!("{}={}", name, value)));
+                    lines
+                }
+                (Some(assignment), None) if assignment.contains('=') => {
+                    self.set_variable(assignment)?;
+                    Vec::new()
+                }
+                (Some(name), Some(value)) => {
+                    self.config.set(name, value)?;
+                    self.history.set_limit(self.config.history_limit);
+                    Vec::new()
+                }
+                (Some(_), None) => return Err("usage: set <setting> <value>".into()),
+            },
+            "alias" => match (args.get(0), args.get(1)) {
+                (None, _) => self.aliases(|_| true),
+                (Some(name), None) => match self.aliases(|n| n == name) {
+                    lines if lines.is_empty() => return Err("no such alias".into()),
+                    lines => lines,
+                },
+                (Some(name), Some(template)) => {
+                    if !args::is_name(name) {
+                        return Err("bad alias name".into());
+                    }
+                    if template.is_empty() {
+                        return Err("usage: alias <name> = <command line>".into());
+                    }
+                    self.config.aliases.insert(name.into(), template.into());
+                    Vec::new()
+                }
+            },
+            "unalias" => {
+                for name in &args.positional {
+                    self.config.aliases.remove(name).ok_or("no such alias")?;
+                }
+                Vec::new()
+            }
+            "rm" => {
+                for name in &args.positional {
+                    self.buffers.remove(name).ok_or("no such buffer")?;
+                }
+                Vec::new()
+            }
+            // asked once the command line is done, at the next prompt
+            "resize" => {
+                self.probe = true;
+                Vec::new()
+            }
+            "jobs" => self.jobs.iter().map(|job| format!("[{}] running  {}", job.id, job.line)).collect(),
+            "kill" => {
+                if args.positional.is_empty() {
+                    return Err("usage: kill %<job>...".into());
+                }
+                let mut lines = Vec::new();
+                for spec in &args.positional {
+                    let mut job = self.jobs.remove(self.job_index(Some(spec))?);
+                    job.pipeline.cancel_before(job.pipeline.stages.len());
+                    lines.push(format!("[{}] killed  {}", job.id, job.line));
+                }
+                lines
+            }
+            // on their own, they are run by `start`
+            "fg" | "wait" => return Err(format!("{} cannot be in a pipeline", name).into()),
+            _ => {
+                let command = self.commands.get(&name).ok_or_else(|| self.unknown_command(&name))?;
+                args.terminal = self.size();
+                return Ok(command.exec(args));
+            }
+        };
+        Ok(Box::new(Lines::from(lines)))
+    }
+
+    /// Command line with its variables and aliases replaced, except for the template of an alias definition
+    fn parse(&self, line: &str) -> Result<CommandLine, &'static str> {
+        let Some((name, template)) = args::alias_definition(line) else {
+            return CommandLine::parse(line, &|name| self.var(name), &self.config.aliases);
+        };
+        let args = Args { positional: Vec::from([name.into(), template.into()]), ..Args::default() };
+        Ok(CommandLine {
+            pipeline: Vec::from([SimpleCommand { name: "alias".into(), args }]),
+            input: InputFrom::Nothing,
+            output: None,
+            background: false,
+        })
+    }
+
+    /// Value of `$name` in a command line
+    fn var(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.status.to_string()),
+            "DURATION" => self.duration.map(|duration| duration.to_string()),
+            "HEAP_FREE" => self.heap_stats.map(|heap_stats| heap_stats().free.to_string()),
+            "CORE" => self.core_id.map(|core_id| core_id().to_string()),
+            "ROWS" => Some(self.size().rows.to_string()),
+            "COLUMNS" => Some(self.size().cols.to_string()),
+            _ => self.variables.get(name).cloned(),
+        }
+    }
+
+    /// `NAME=value`, an empty value removing the variable
+    fn set_variable(&mut self, assignment: &str) -> Result<(), &'static str> {
+        let (name, value) = assignment.split_once('=').unwrap_or((assignment, ""));
+        if !args::is_name(name) {
+            return Err("bad variable name");
+        }
+        if BUILTIN_VARIABLES.contains(&name) {
+            return Err("read-only variable");
+        }
+        match value {
+            "" => self.variables.remove(name),
+            _ => self.variables.insert(name.into(), value.into()),
+        };
+        Ok(())
+    }
+
+    fn aliases(&self, filter: impl Fn(&str) -> bool) -> Vec<String> {
+        self.config
+            .aliases
+            .iter()
+            .filter(|(name, _)| filter(name))
+            .map(|(name, template)| format!("{} = {}", name, template))
+            .collect()
+    }
+
+    fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        BUILTINS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(self.commands.names.iter().copied())
+    }
+
+    fn help(&self, name: Option<&str>) -> Result<Vec<String>, Cow<'static, str>> {
+        let Some(name) = name else {
+            let width = self.names().map(str::len).max().unwrap_or_default();
+            let helps = BUILTINS.iter().map(|(_, help)| help).chain(self.commands.helps.iter());
+            return Ok(self
+                .names()
+                .zip(helps)
+                .map(|(name, help)| format!("{:<width$}  {}", name, help.summary))
+                .collect());
+        };
+        BUILTINS
+            .iter()
+            .find_map(|(n, help)| (*n == name).then_some(help))
+            .or_else(|| self.commands.help(name))
+            .map(CommandHelp::lines)
+            .ok_or_else(|| self.unknown_command(name))
+    }
+
+    fn unknown_command(&self, name: &str) -> Cow<'static, str> {
+        let closest = self
+            .names()
+            .map(|n| (distance(name, n), n))
+            // on a tie, a typo is more likely after the first letter
+            .min_by_key(|(d, n)| (*d, n.chars().next() != name.chars().next()));
+        match closest {
+            Some((d, n)) if d <= 2 && d < n.len() => {
+                format!("unknown command, did you mean {}?", n).into()
+            }
+            _ => "unknown command".into(),
+        }
+    }
+
+    fn complete(&self, line: &str) -> Vec<String> {
+        let mut words: Vec<&str> = line.split(' ').collect();
+        let prefix = words.pop().unwrap_or_default();
+        let Some((&name, args)) = words.split_first() else {
+            return self
+                .names()
+                .map(ToString::to_string)
+                .chain(self.config.aliases.keys().cloned())
+                .filter(|n| n.starts_with(prefix))
+                .collect();
+        };
+        if let Some(buffer) = prefix.strip_prefix('@') {
+            return self
+                .buffers
+                .names()
+                .filter(|n| n.starts_with(buffer))
+                .map(|n| format!("@{}", n))
+                .collect();
+        }
+        if name == "rm" || name == "source" {
+            return self
+                .buffers
+                .names()
+                .filter(|n| n.starts_with(prefix))
+                .map(ToString::to_string)
+                .collect();
+        }
+        if name == "set" {
+            return ConsoleConfig::complete(args, prefix);
+        }
+        if matches!(name, "fg" | "wait" | "kill") {
+            return self
+                .jobs
+                .iter()
+                .map(|job| format!("%{}", job.id))
+                .filter(|spec| spec.starts_with(prefix))
+                .collect();
+        }
+        if name == "unalias" || (name == "alias" && args.is_empty()) {
+            return self
+                .config
+                .aliases
+                .keys()
+                .filter(|n| n.starts_with(prefix))
+                .cloned()
+                .collect();
+        }
+        if name == "help" && args.is_empty() {
+            return self
+                .names()
+                .filter(|n| n.starts_with(prefix))
+                .map(ToString::to_string)
+                .collect();
+        }
+        match self.commands.get(name) {
+            Some(command) => command.complete(args, prefix),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn common_prefix(candidates: &[String]) -> &str {
+    let Some((first, others)) = candidates.split_first() else {
+        return "";
+    };
+    let len = others.iter().fold(first.len(), |len, c| {
+        first[..len]
+            .char_indices()
+            .zip(c.chars())
+            .find_map(|((i, a), b)| (a != b).then_some(i))
+            .unwrap_or(len.min(c.len()))
+    });
+    &first[..len]
+}
+
+/// Levenshtein distance between two command names
+fn distance(a: &str, b: &str) -> usize {
+    let mut row: Vec<usize> = (0..=b.chars().count()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.chars().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[row.len() - 1]
+}
+
+const BUILTINS: &[(&str, CommandHelp)] = &[
+    (
+        "help",
+        CommandHelp {
+            summary: "list commands, or show how to use one",
+            usage: "help [<command>]",
+            args: &[("<command>", "name of the command")],
+        },
+    ),
+    (
+        "history",
+        CommandHelp {
+            summary: "list previous command lines",
+            usage: "history",
+            args: &[],
+        },
+    ),
+    (
+        "echo",
+        CommandHelp {
+            summary: "output its arguments",
+            usage: "echo <text>...",
+            args: &[("<text>", "text to output, $? being the exit status of the previous command line")],
+        },
+    ),
+    (
+        "cat",
+        CommandHelp {
+            summary: "output input lines",
+            usage: "cat < @<name>",
+            args: &[("@<name>", "buffer to show")],
+        },
+    ),
+    (
+        "load",
+        CommandHelp {
+            summary: "save input lines in a buffer",
+            usage: "load <name> <",
+            args: &[("<name>", "buffer name, used later as < @<name>")],
+        },
+    ),
+    (
+        "ls",
+        CommandHelp {
+            summary: "list buffers with their size",
+            usage: "ls",
+            args: &[],
+        },
+    ),
+    (
+        "rm",
+        CommandHelp {
+            summary: "delete buffers",
+            usage: "rm <name>...",
+            args: &[("<name>", "buffer name")],
+        },
+    ),
+    (
+        "grep",
+        CommandHelp {
+            summary: "keep input lines containing a text",
+            usage: "grep <text>",
+            args: &[("<text>", "text to search, quoted if it contains spaces")],
+        },
+    ),
+    (
+        "head",
+        CommandHelp {
+            summary: "keep the first input lines",
+            usage: "head [-n <lines>]",
+            args: &[("-n <lines>", "number of lines to keep, 10 by default")],
+        },
+    ),
+    (
+        "more",
+        CommandHelp {
+            summary: "show input lines a screenful at a time, at the end of a command line",
+            usage: "<command> | more",
+            args: &[("space", "key showing the next screenful"), ("Enter", "key showing the next line"), ("q", "key stopping the command line")],
+        },
+    ),
+    (
+        "source",
+        CommandHelp {
+            summary: "run the command lines of a buffer, until one fails",
+            usage: "source @<name>",
+            args: &[("@<name>", "buffer with command lines, followed by input lines for commands reading some")],
+        },
+    ),
+    (
+        "set",
+        CommandHelp {
+            summary: "show or change how the console looks",
+            usage: "set [<setting> <value> | <name>=<value>]",
+            args: &[
+                ("<setting>", "prompt, input-prompt, output-prompt, error-prompt, type-ahead-prompt, echo, newline, color, input-limit, history-limit, rows or cols"),
+                ("<value>", "quoted text for prompts, on/off for echo and color, crlf/lf for newline, bytes or off for input-limit, bytes for history-limit, size assumed for rows and cols"),
+                ("<name>=<value>", "variable read with $name or ${name}, removed by an empty value"),
+            ],
+        },
+    ),
+    (
+        "resize",
+        CommandHelp {
+            summary: "ask the terminal for its size, read with $ROWS and $COLUMNS",
+            usage: "resize",
+            args: &[],
+        },
+    ),
+    (
+        "alias",
+        CommandHelp {
+            summary: "list or define command names replaced by a command line",
+            usage: "alias [<name> [= <command line>]]",
+            args: &[("<command line>", "kept as typed, $1 to $9 being the words after the name, added at the end otherwise")],
+        },
+    ),
+    (
+        "unalias",
+        CommandHelp {
+            summary: "delete aliases",
+            usage: "unalias <name>...",
+            args: &[],
+        },
+    ),
+    (
+        "jobs",
+        CommandHelp {
+            summary: "list command lines started with &, whose output goes to buffer %<job>",
+            usage: "jobs",
+            args: &[],
+        },
+    ),
+    (
+        "fg",
+        CommandHelp {
+            summary: "show the rest of the output of a job, until it finishes",
+            usage: "fg [%<job>]",
+            args: &[("%<job>", "job number, the last job started by default")],
+        },
+    ),
+    (
+        "wait",
+        CommandHelp {
+            summary: "wait for a job to finish, or for all of them",
+            usage: "wait [%<job>]",
+            args: &[("%<job>", "job number, its exit status being the one of wait")],
+        },
+    ),
+    (
+        "kill",
+        CommandHelp {
+            summary: "stop jobs",
+            usage: "kill %<job>...",
+            args: &[("%<job>", "job number")],
+        },
+    ),
+];
+
+/// Output of the first future to complete, `Ok` for `f1` and `Err` for `f2`
+fn first_of<T, U>(
+    f1: impl Future<Output = T>,
+    f2: impl Future<Output = U>,
+) -> impl Future<Output = Result<T, U>> {
+    let (mut p1, mut p2) = (Box::pin(f1), Box::pin(f2));
+    poll_fn(move |cx| {
+        if let Poll::Ready(t) = p1.as_mut().poll(cx) {
+            Poll::Ready(Ok(t))
+        } else if let Poll::Ready(u) = p2.as_mut().poll(cx) {
+            Poll::Ready(Err(u))
+        } else {
+            Poll::Pending
+        }
+    })
+}
+
+const EOL_NONE : &[u8] = b"";
+
+// set by the console, `?` being the only one that is not a name
+const BUILTIN_VARIABLES: &[&str] = &["DURATION", "HEAP_FREE", "CORE", "ROWS", "COLUMNS"];
+
+// exit status of a command line that could not run, or stopped with Ctrl-C
+const STATUS_ERROR : u8 = 1;
+const STATUS_CANCELLED : u8 = 130;
+// before the exit status of each command line of a script
+const SCRIPT_STATUS : &str = "? ";
+
+const COLS : usize = 128;
+const COLS_SHRINK : usize = 32;
+// heap left to the commands once their input is read
+const HEAP_RESERVE : usize = 16 * 1024;
+
+impl<I: AsyncInputIterator> Console<I> {
+    /// Keep what is typed while a command runs, without running it when the command finishes
+    fn type_ahead(&mut self, input: Input) -> Vec<u8> {
+        let mut echo = Vec::new();
+        if self.type_ahead.is_none() {
+            echo.extend(self.config.marker(Marker::TypeAhead));
+            self.type_ahead = Some(self.editor(Marker::TypeAhead));
+        }
+        let Some(typed) = &mut self.type_ahead else {
+            return Vec::new();
+        };
+        match input {
+            // the line is only run after Enter at the prompt
+            Input::Line(s) | Input::IncompleteLine(s) => echo.extend(typed.insert(&s)),
+            other => echo.extend(Edit::from_input(&other).map(|edit| typed.edit(edit)).unwrap_or_default()),
+        }
+        self.config.echo(echo)
+    }
+
+    /// Output of a command, above the line being typed if any
+    fn show(&self, pipeline: &Pipeline, marker: Marker, text: &str) -> (Vec<u8>, Vec<u8>) {
+        match (pipeline.framing, marker) {
+            (Framing::Terminal, _) => {}
+            (Framing::Script, _) => return (self.config.framed(self.config.prompt_of(marker), text), Vec::new()),
+            (Framing::Frames, Marker::Error) => return (Response::Error(text).encode(), Vec::new()),
+            (Framing::Frames, _) => return (Response::Line(text).encode(), Vec::new()),
+        }
+        let text = match marker {
+            Marker::Error => self.config.error(text),
+            _ => self.config.output(text),
+        };
+        let below = match (&pipeline.input, &self.type_ahead) {
+            (Some(input), _) => Some((Marker::Input, &input.line)),
+            (None, Some(typed)) => Some((Marker::TypeAhead, typed)),
+            (None, None) => None,
+        };
+        match below {
+            None => (self.config.marker(marker), text),
+            Some((below, editor)) => {
+                let mut out = self.config.echo(editor.to_first_row());
+                out.extend(self.config.replace_line(marker));
+                out.extend(text);
+                out.extend(self.config.marker(below));
+                out.extend(self.config.echo(editor.redisplay()));
+                (out, Vec::new())
+            }
+        }
+    }
+
+    /// Count the terminal rows of an output line, false when `more` holds it until a key is pressed
+    fn fits_screen(&self, pipeline: &mut Pipeline, marker: Marker, text: &str) -> bool {
+        let Some(pager) = &mut pipeline.pager else {
+            return true;
+        };
+        pager.fits(unicode::width(self.config.prompt_of(marker)) + unicode::width(text))
+    }
+
+    /// Keep a line that does not fit on the screen, showing `MORE` below the others instead
+    fn hold(&mut self, mut pipeline: Pipeline, marker: Marker, text: String) -> Vec<u8> {
+        if let Some(pager) = &mut pipeline.pager {
+            pager.held = Some((marker, text));
+        }
+        self.state = ConsoleState::RunningCommand(pipeline);
+        format!("{}{}", self.config.newline(), pager::MORE).into_bytes()
+    }
+
+    /// Key typed while `more` is paused: space shows the next screenful, Enter the next line and `q` stops
+    fn page(&self, pipeline: &mut Pipeline, input: Input) -> Vec<u8> {
+        let Some(pager) = &mut pipeline.pager else {
+            return Vec::new();
+        };
+        let key = match &input {
+            Input::Line(s) if s.is_empty() => '\r',
+            Input::Line(s) | Input::IncompleteLine(s) => s.chars().next().unwrap_or_default(),
+            _ => return Vec::new(),
+        };
+        let screenful = match key {
+            ' ' => true,
+            '\r' => false,
+            'q' => {
+                // ends as if the commands had, with the status of `more`
+                let last = pipeline.stages.len() - 1;
+                pipeline.cancel_before(last + 1);
+                pipeline.stages[last] = Some(Box::new(Lines::from(Vec::new())));
+                pipeline.status = 0;
+                pipeline.pager = None;
+                return pager::ERASE_MORE.into();
+            }
+            _ => return b"\x07".into(),
+        };
+        let Some((marker, text)) = pager.held.take() else {
+            return Vec::new();
+        };
+        pager.resume(screenful, unicode::width(self.config.prompt_of(marker)) + unicode::width(&text));
+        let mut out = pager::ERASE_MORE.to_vec();
+        let (before, line) = self.show(pipeline, marker, &text);
+        out.extend(before);
+        out.extend(line);
+        out
+    }
+
+    /// Size reported by the terminal, answering `SIZE_QUERY`, for the line typed below the output
+    fn resize(&mut self, size: TerminalSize) -> TerminalSize {
+        self.size = Some(size);
+        if let Some(typed) = &mut self.type_ahead {
+            typed.resize(size.cols.into());
+        }
+        size
+    }
+
+    /// Line of a job above the line being typed at the prompt
+    fn above_prompt(&self, marker: Marker, text: &str, prompt: &LineEditor) -> Vec<u8> {
+        let mut out = self.config.echo(prompt.to_first_row());
+        out.extend(self.config.replace_line(marker));
+        out.extend(match marker {
+            Marker::Error => self.config.error(text),
+            _ => self.config.output(text),
+        });
+        out.extend(self.config.marker(Marker::Prompt));
+        out.extend(self.config.echo(prompt.redisplay()));
+        out
+    }
+
+    /// Start the next command line of the script, with its input lines
+    fn script_step(&mut self) -> Vec<u8> {
+        self.state = ConsoleState::Prompt(self.editor(Marker::Prompt));
+        let Some(line) = self.script.as_mut().and_then(Script::next_command) else {
+            return self.end_script();
+        };
+        let mut out = self.config.framed(&self.config.prompt, &line);
+        let started = self
+            .parse(&line)
+            .map_err(Cow::from)
+            .and_then(|command| self.start(command, &line));
+        match started {
+            Ok(mut pipeline) => {
+                pipeline.framing = Framing::Script;
+                if let Some(ConsoleInput { terminator, .. }) = &pipeline.input {
+                    let input = self.script.as_mut().map(|s| s.input(terminator.as_deref())).unwrap_or_default();
+                    pipeline.feed_all(input);
+                }
+                self.state = ConsoleState::RunningCommand(pipeline);
+            }
+            Err(err) => {
+                self.status = STATUS_ERROR;
+                out.extend(self.config.framed(&self.config.error_prompt, &err));
+                out.extend(self.script_status());
+            }
+        }
+        out
+    }
+
+    /// Answer a message of the host in framed mode
+    fn request(&mut self, message: &[u8]) -> Vec<u8> {
+        self.state = ConsoleState::Framed;
+        let (status, err): (u8, Cow<'static, str>) = match Request::decode(message) {
+            Some(Request::Run { name, args, input }) => {
+                let line = name.clone();
+                let command = CommandLine {
+                    pipeline: Vec::from([SimpleCommand { name, args: args.into_iter().collect() }]),
+                    input: InputFrom::Console,
+                    output: None,
+                    background: false,
+                };
+                match self.start(command, &line) {
+                    Ok(mut pipeline) => {
+                        pipeline.framing = Framing::Frames;
+                        pipeline.feed_all(script::lines(&input));
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        return Vec::new();
+                    }
+                    Err(err) => (STATUS_ERROR, err),
+                }
+            }
+            // nothing to cancel
+            Some(Request::Cancel) => return Response::Status(self.status).encode(),
+            Some(Request::Quit) => {
+                self.state = ConsoleState::Prompt(self.editor(Marker::Prompt));
+                return self.config.marker(Marker::Prompt);
+            }
+            None => (STATUS_ERROR, "bad request".into()),
+        };
+        self.status = status;
+        let mut out = Response::Error(&err).encode();
+        out.extend(Response::Status(status).encode());
+        out
+    }
+
+    /// Exit status of a command line of the script, which stops if it failed
+    fn script_status(&mut self) -> Vec<u8> {
+        let mut out = self.config.framed(SCRIPT_STATUS, &self.status.to_string());
+        if self.status != 0 {
+            out.extend(self.end_script());
+        }
+        out
+    }
+
+    fn end_script(&mut self) -> Vec<u8> {
+        match self.script.take() {
+            // back at the start of a line
+            Some(Script { interactive: true, .. }) => self.config.prompt.as_bytes().to_vec(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub async fn next_wait(&mut self) -> (Cow<'_, [u8]>, Cow<'_, [u8]>) {
+        match core::mem::replace(&mut self.state, ConsoleState::Poisoned) {
+            ConsoleState::RunningCommand(mut pipeline) => {
+                let paused = pipeline.pager.as_ref().is_some_and(|pager| pager.waiting());
+                // input left unread, the first command not taking more
+                let holding = Cell::new(pipeline.holds_input());
+                let (clock, timer) = (self.clock, self.timer);
+                let outputs = async {
+                    if paused {
+                        // the commands wait for a key too
+                        pending::<()>().await;
+                    }
+                    // woken by the answer of the terminal, read as input, or by the timer
+                    poll_fn(|cx| match pipeline.pager.as_mut().map(|pager| (pager.probing(clock), pager.deadline())) {
+                        Some((true, deadline)) => {
+                            if let Some((timer, deadline)) = timer.zip(deadline) {
+                                timer(deadline, cx.waker());
+                            }
+                            Poll::Pending
+                        }
+                        _ => Poll::Ready(()),
+                    })
+                    .await;
+                    if !holding.get() {
+                        return pipeline.next().await;
+                    }
+                    // polled afresh until the first command takes input again
+                    poll_fn(|cx| {
+                        let next = core::pin::pin!(pipeline.next()).poll(cx);
+                        holding.set(pipeline.holds_input());
+                        next
+                    })
+                    .await
+                };
+                let input = async {
+                    poll_fn(|_| match holding.get() {
+                        true => Poll::Pending,
+                        false => Poll::Ready(()),
+                    })
+                    .await;
+                    self.input.next_wait().await
+                };
+                let next = first_of(first_of(outputs, input), next_job(&mut self.jobs)).await;
+                let next = match next {
+                    Ok(next) => next,
+                    Err((job, idx, output)) => {
+                        let out = match self.job_event(job, idx, output) {
+                            JobEvent::Nothing => (Vec::new(), Vec::new()),
+                            JobEvent::Error(err) => self.show(&pipeline, Marker::Error, &err),
+                            JobEvent::Done { id, status, notice } => {
+                                match pipeline.waiting {
+                                    Some(Waiting::Job(waited)) if waited == id => {
+                                        pipeline.status = status;
+                                        pipeline.feed(None);
+                                    }
+                                    Some(Waiting::All) if self.jobs.is_empty() => pipeline.feed(None),
+                                    _ => {}
+                                }
+                                self.show(&pipeline, Marker::Run, &notice)
+                            }
+                        };
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        return (out.0.into(), out.1.into());
+                    }
+                };
+                let last = pipeline.stages.len() - 1;
+                match next {
+                    Ok((idx, Some(Output::Exit(status)))) => {
+                        // as in sh, the status of a pipeline is the one of its last command
+                        if idx == last {
+                            pipeline.status = status;
+                        }
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (EOL_NONE.into(), EOL_NONE.into())
+                    }
+                    Ok((idx, Some(Output::Line(line)))) if idx < last => {
+                        if let Some(command) = &mut pipeline.stages[idx + 1] {
+                            command.input(Some(line));
+                        }
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (EOL_NONE.into(), EOL_NONE.into())
+                    }
+                    Ok((_, Some(Output::Line(line)))) if pipeline.output.is_some() => {
+                        pipeline.collected.push(line);
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (EOL_NONE.into(), EOL_NONE.into())
+                    }
+                    Ok((_, Some(Output::Line(line)))) if !self.fits_screen(&mut pipeline, Marker::Run, &line) => {
+                        (self.hold(pipeline, Marker::Run, line).into(), EOL_NONE.into())
+                    }
+                    Ok((_, Some(Output::Error(err)))) if !self.fits_screen(&mut pipeline, Marker::Error, &err) => {
+                        (self.hold(pipeline, Marker::Error, err).into(), EOL_NONE.into())
+                    }
+                    Ok((_, Some(Output::Line(line)))) => {
+                        let out = self.show(&pipeline, Marker::Run, &line);
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (out.0.into(), out.1.into())
+                    }
+                    // errors are not given to the next command, nor saved in a buffer
+                    Ok((_, Some(Output::Error(err)))) => {
+                        let out = self.show(&pipeline, Marker::Error, &err);
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (out.0.into(), out.1.into())
+                    }
+                    Ok((idx, None)) if idx < last => {
+                        let reading = pipeline.input.is_some();
+                        pipeline.stages[idx] = None;
+                        pipeline.cancel_before(idx);
+                        if let Some(command) = &mut pipeline.stages[idx + 1] {
+                            command.input(None);
+                        }
+                        let eol = match reading && pipeline.input.is_none() {
+                            true => self.config.marker(Marker::InputEnd),
+                            false => Vec::new(),
+                        };
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (eol.into(), EOL_NONE.into())
+                    }
+                    Ok((_, None)) => {
+                        let eol = match pipeline.input {
+                            Some(_) => self.config.marker(Marker::InputPrompt),
+                            // `source` started a script
+                            None if self.script.is_some() => self.config.newline().into(),
+                            None => self.config.marker(Marker::Prompt),
+                        };
+                        pipeline.cancel_before(last);
+                        self.status = pipeline.status;
+                        self.duration = pipeline.started.zip(self.clock).map(|(started, clock)| clock() - started);
+                        if let Some(name) = pipeline.output {
+                            self.buffers.insert(name, pipeline.collected);
+                        }
+                        match pipeline.framing {
+                            Framing::Terminal => {}
+                            Framing::Script => {
+                                self.state = ConsoleState::Prompt(self.editor(Marker::Prompt));
+                                return (self.script_status().into(), EOL_NONE.into());
+                            }
+                            Framing::Frames => {
+                                self.state = ConsoleState::Framed;
+                                return (Response::Status(self.status).encode().into(), EOL_NONE.into());
+                            }
+                        }
+                        match self.type_ahead.take() {
+                            Some(mut typed) => {
+                                // the prompt replaces the type-ahead line
+                                let mut out = self.config.echo(typed.to_first_row());
+                                out.extend(self.config.replace_line(Marker::Prompt));
+                                typed.set_prompt_width(unicode::width(&self.config.prompt));
+                                out.extend(self.config.echo(typed.redisplay()));
+                                self.state = ConsoleState::Prompt(typed);
+                                (out.into(), EOL_NONE.into())
+                            }
+                            None => {
+                                self.state = ConsoleState::Prompt(self.editor(Marker::Prompt));
+                                (eol.into(), EOL_NONE.into())
+                            }
+                        }
+                    }
+                    Err(Input::Control('\x03')) => {
+                        let eol = match pipeline.input {
+                            Some(_) => self.config.marker(Marker::InputCancel),
+                            None => self.config.marker(Marker::Cancel),
+                        };
+                        pipeline.cancel_before(last + 1);
+                        self.status = STATUS_CANCELLED;
+                        self.type_ahead = None;
+                        self.script = None;
+                        self.state = ConsoleState::Prompt(self.editor(Marker::Prompt));
+                        (eol.into(), EOL_NONE.into())
+                    }
+                    Err(Input::Frame(message)) if pipeline.framing == Framing::Frames => {
+                        match Request::decode(&message) {
+                            Some(request @ (Request::Cancel | Request::Quit)) => {
+                                pipeline.cancel_before(last + 1);
+                                self.status = STATUS_CANCELLED;
+                                let mut out = Response::Status(self.status).encode();
+                                if request == Request::Quit {
+                                    out.extend(self.config.marker(Marker::Prompt));
+                                    self.state = ConsoleState::Prompt(self.editor(Marker::Prompt));
+                                } else {
+                                    self.state = ConsoleState::Framed;
+                                }
+                                (out.into(), EOL_NONE.into())
+                            }
+                            // one request at a time
+                            _ => {
+                                self.state = ConsoleState::RunningCommand(pipeline);
+                                (Response::Error("a command is running").encode().into(), EOL_NONE.into())
+                            }
+                        }
+                    }
+                    Err(Input::EscapeSequence(
+                        EscapeSequence::CursorPosition { row: rows, col: cols } | EscapeSequence::TextAreaSize { rows, cols },
+                    )) => {
+                        let size = self.resize(TerminalSize { rows, cols });
+                        if let Some(pager) = &mut pipeline.pager {
+                            pager.resize(size);
+                        }
+                        if let Some(input) = &mut pipeline.input {
+                            input.line.resize(size.cols.into());
+                        }
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (EOL_NONE.into(), EOL_NONE.into())
+                    }
+                    Err(input) if paused => {
+                        let out = self.page(&mut pipeline, input);
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (out.into(), EOL_NONE.into())
+                    }
+                    // only Ctrl-C stops a script
+                    Err(_) if pipeline.framing != Framing::Terminal => {
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (EOL_NONE.into(), EOL_NONE.into())
+                    }
+                    Err(input) => {
+                        let Some(ConsoleInput { terminator, line: current_line, bytes, pasting }) = &mut pipeline.input else {
+                            let echo = self.type_ahead(input);
+                            self.state = ConsoleState::RunningCommand(pipeline);
+                            return (echo.into(), EOL_NONE.into());
+                        };
+                        let mut over = None;
+                        let (echo, eol) = match input {
+                            Input::Line(s) => {
+                                let mut echo = current_line.insert(&s);
+                                echo.extend(current_line.to_last_row());
+                                let mut line = current_line.take();
+                                if terminator.as_deref() == Some(line.as_str()) {
+                                    pipeline.feed(None);
+                                    (echo, self.config.marker(Marker::InputEnd))
+                                } else if let Err(err) = self.check_input(*bytes, &line) {
+                                    over = Some(err);
+                                    (echo, Vec::new())
+                                } else {
+                                    *bytes += line.len();
+                                    if line.len() < COLS_SHRINK {
+                                        line.shrink_to_fit();
+                                    }
+                                    pipeline.feed(Some(line));
+                                    (echo, self.config.marker(Marker::Input))
+                                }
+                            }
+                            Input::IncompleteLine(s) => {
+                                let echo = current_line.insert(&s);
+                                // a paste without newlines only grows the line
+                                over = self.check_input(*bytes, current_line.as_str()).err();
+                                (echo, Vec::new())
+                            }
+                            Input::EscapeSequence(EscapeSequence::PasteStart) => {
+                                *pasting = true;
+                                (Vec::new(), Vec::new())
+                            }
+                            Input::EscapeSequence(EscapeSequence::PasteEnd) => {
+                                *pasting = false;
+                                (Vec::new(), Vec::new())
+                            }
+                            // a heredoc only ends on its terminator line
+                            Input::Control('\x04') if terminator.is_none() => {
+                                let line = current_line.take();
+                                if !line.is_empty() {
+                                    pipeline.feed(Some(line));
+                                }
+                                pipeline.feed(None);
+                                (Vec::new(), self.config.marker(Marker::InputEnd))
+                            }
+                            other => {
+                                let echo = Edit::from_input(&other)
+                                    .map(|edit| current_line.edit(edit))
+                                    .unwrap_or_default();
+                                (echo, Vec::new())
+                            }
+                        };
+                        if let Some(err) = over {
+                            // the rest of the paste would be run as command lines otherwise
+                            self.skip_paste = pipeline.input.as_ref().is_some_and(|input| input.pasting);
+                            pipeline.cancel_before(last + 1);
+                            self.type_ahead = None;
+                            self.script = None;
+                            self.state = ConsoleState::Error(err.into());
+                            return (self.config.echo(echo).into(), self.config.marker(Marker::InputError).into());
+                        }
+                        self.state = ConsoleState::RunningCommand(pipeline);
+                        (self.config.echo(echo).into(), eol.into())
+                    }
+                }
+            }
+            ConsoleState::Error(err) => {
+                self.status = STATUS_ERROR;
+                self.state = ConsoleState::Prompt(self.editor(Marker::Prompt));
+                (self.config.error(&err).into(), self.config.marker(Marker::Prompt).into())
+            }
+            ConsoleState::Prompt(_) if self.script.is_some() => (self.script_step().into(), EOL_NONE.into()),
+            ConsoleState::Prompt(prompt) if self.probe => {
+                self.probe = false;
+                self.state = ConsoleState::Prompt(prompt);
+                (config::SIZE_QUERY.into(), EOL_NONE.into())
+            }
+            ConsoleState::Framed => {
+                let out = match self.input.next_wait().await {
+                    Input::Frame(message) => self.request(&message),
+                    _ => {
+                        self.state = ConsoleState::Framed;
+                        let mut out = Response::Error("bad frame").encode();
+                        out.extend(Response::Status(STATUS_ERROR).encode());
+                        out
+                    }
+                };
+                (out.into(), EOL_NONE.into())
+            }
+            ConsoleState::Prompt(mut prompt) => {
+                let input = match first_of(self.input.next_wait(), next_job(&mut self.jobs)).await {
+                    Ok(input) => input,
+                    Err((job, idx, output)) => {
+                        let out = match self.job_event(job, idx, output) {
+                            JobEvent::Nothing => Vec::new(),
+                            JobEvent::Error(err) => self.above_prompt(Marker::Error, &err, &prompt),
+                            JobEvent::Done { notice, .. } => self.above_prompt(Marker::Run, &notice, &prompt),
+                        };
+                        self.state = ConsoleState::Prompt(prompt);
+                        return (out.into(), EOL_NONE.into());
+                    }
+                };
+                let last_tab = core::mem::replace(&mut self.last_tab, false);
+                match input {
+                    Input::EscapeSequence(EscapeSequence::PasteEnd) if self.skip_paste => {
+                        self.skip_paste = false;
+                        self.state = ConsoleState::Prompt(prompt);
+                        (EOL_NONE.into(), EOL_NONE.into())
+                    }
+                    Input::Control('\x03') if self.skip_paste => {
+                        self.skip_paste = false;
+                        self.state = ConsoleState::Prompt(prompt);
+                        (self.config.marker(Marker::Cancel).into(), EOL_NONE.into())
+                    }
+                    _ if self.skip_paste => {
+                        self.state = ConsoleState::Prompt(prompt);
+                        (EOL_NONE.into(), EOL_NONE.into())
+                    }
+                    Input::EscapeSequence(
+                        EscapeSequence::CursorPosition { row: rows, col: cols } | EscapeSequence::TextAreaSize { rows, cols },
+                    ) => {
+                        let size = self.resize(TerminalSize { rows, cols });
+                        prompt.resize(size.cols.into());
+                        self.state = ConsoleState::Prompt(prompt);
+                        (EOL_NONE.into(), EOL_NONE.into())
+                    }
+                    Input::Control('\x03') => {
+                        self.state = ConsoleState::Prompt(self.editor(Marker::Prompt));
+                        (self.config.marker(Marker::Cancel).into(), EOL_NONE.into())
+                    }
+                    Input::EscapeSequence(EscapeSequence::FramedMode) => {
+                        self.input.start_frames();
+                        self.state = ConsoleState::Framed;
+                        // the zero byte ends what the host received before
+                        let mut out = Vec::from([0]);
+                        out.extend(Response::READY.encode());
+                        (out.into(), EOL_NONE.into())
+                    }
+                    Input::Control('\t') => {
+                        let echo = self.tab_complete(&mut prompt, last_tab);
+                        self.last_tab = true;
+                        self.state = ConsoleState::Prompt(prompt);
+                        (self.config.echo(echo).into(), EOL_NONE.into())
+                    }
+                    Input::Line(s) => {
+                        let mut echo = prompt.insert(&s);
+                        echo.extend(prompt.to_last_row());
+                        let mut prompt = prompt.take();
+                        match self.history.expand(&prompt) {
+                            Ok(None) => {}
+                            Ok(Some(expanded)) => {
+                                echo.extend_from_slice(self.config.newline().as_bytes());
+                                echo.extend_from_slice(expanded.as_bytes());
+                                prompt = expanded;
+                            }
+                            Err(err) => {
+                                self.state = ConsoleState::Error(err.into());
+                                return (self.config.echo(echo).into(), self.config.marker(Marker::Error).into());
+                            }
+                        }
+                        self.history.push(&prompt);
+                        let command = match self.parse(&prompt) {
+                            Ok(command) => command,
+                            Err(err) => {
+                                self.state = ConsoleState::Error(err.into());
+                                return (self.config.echo(echo).into(), self.config.marker(Marker::Error).into());
+                            }
+                        };
+                        // paged at the terminal only, scripts and frames getting the lines of `more` at once
+                        let paged = command.output.is_none() && command.pipeline.last().is_some_and(|c| c.name == "more");
+                        let eol = match self.start(command, &prompt) {
+                            Ok(mut pipeline) => {
+                                // without a timer to end it, the wait for the size of the terminal would not end
+                                let asked = self.clock.zip(self.timer).map(|(clock, _)| clock());
+                                pipeline.pager = paged.then(|| Box::new(Pager::new(self.size(), asked)));
+                                let mut eol = self.config.marker(match pipeline.input {
+                                    Some(_) => Marker::InputStart,
+                                    None => Marker::Run,
+                                });
+                                if pipeline.pager.is_some() {
+                                    eol.extend_from_slice(config::SIZE_QUERY);
+                                }
+                                self.state = ConsoleState::RunningCommand(pipeline);
+                                eol
+                            }
+                            Err(err) => {
+                                self.state = ConsoleState::Error(err);
+                                self.config.marker(Marker::Error)
+                            }
+                        };
+                        (self.config.echo(echo).into(), eol.into())
+                    }
+                    Input::IncompleteLine(s) => {
+                        let echo = prompt.insert(&s);
+                        self.state = ConsoleState::Prompt(prompt);
+                        (self.config.echo(echo).into(), EOL_NONE.into())
+                    }
+                    Input::EscapeSequence(EscapeSequence::Key(Key::Up, _)) => {
+                        let echo = match self.history.previous(prompt.as_str()) {
+                            Some(line) => prompt.replace(line),
+                            None => Vec::new(),
+                        };
+                        self.state = ConsoleState::Prompt(prompt);
+                        (self.config.echo(echo).into(), EOL_NONE.into())
+                    }
+                    Input::EscapeSequence(EscapeSequence::Key(Key::Down, _)) => {
+                        let echo = match self.history.next() {
+                            Some(line) => prompt.replace(&line),
+                            None => Vec::new(),
+                        };
+                        self.state = ConsoleState::Prompt(prompt);
+                        (self.config.echo(echo).into(), EOL_NONE.into())
+                    }
+                    other => {
+                        let echo = Edit::from_input(&other)
+                            .map(|edit| prompt.edit(edit))
+                            .unwrap_or_default();
+                        self.state = ConsoleState::Prompt(prompt);
+                        (self.config.echo(echo).into(), EOL_NONE.into())
+                    },
+                }
+            }
+            ConsoleState::Poisoned => unreachable!(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    extern crate std;
+    use alloc::rc::Rc;
+    use std::collections::VecDeque;
+    use std::future::poll_fn;
+    use std::prelude::rust_2015::Vec;
+    use std::sync::Mutex;
+    use std::task::Poll;
+    use super::{AsyncInputQueue, InputQueue};
+
+    #[derive(Clone)]
+    pub struct MutexQueue(Rc<Mutex<(VecDeque<Vec<u8>>, Option<std::task::Waker>)>>);
+    impl MutexQueue {
+        pub fn new() -> Self {
+            Self(Rc::new(Mutex::new((VecDeque::new(), None))))
+        }
+
+        pub fn push(&self, input: Vec<u8>) {
+            let mut inner = self.0.lock().unwrap();
+            inner.0.push_back(input.into());
+            inner.1.take().map(|waker| waker.wake());
+        }
+    }
+    impl InputQueue for MutexQueue {
+        fn pop(&mut self) -> Option<Vec<u8>> {
+            self.0.lock().unwrap().0.pop_front()
+        }
+    }
+    impl AsyncInputQueue for MutexQueue {
+        async fn pop_wait(&mut self) -> Vec<u8> {
+            poll_fn(|ctx| {
+                match self.pop() {
+                    Some(buf) => Poll::Ready(buf),
+                    None => {
+                        self.0.lock().unwrap().1 = Some(ctx.waker().clone());
+                        Poll::Pending
+                    },
+                }
+            }).await
+        }
+    }
+}
+#[cfg(target_os = "linux")]
+pub use linux::*;
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
     fn test_input_parser() {
         let queue = MutexQueue::new();
         let mut parser = InputParser::new(queue.clone());
@@ -510,28 +2500,713 @@ mod tests {
         assert_eq!(parser.next(), None);
     }
 
+    #[test]
+    fn test_input_parser_utf8() {
+        let queue = MutexQueue::new();
+        let mut parser = InputParser::new(queue.clone());
+        queue.push(b"\xc3".to_vec());
+        assert_eq!(parser.next(), None);
+        queue.push(b"\xa9\xe6\x97\xa5\r".to_vec());
+        assert_eq!(parser.next(), Some(Input::Line("é日".into())));
+        // stray continuation, overlong, surrogate, over U+10FFFF, invalid lead
+        queue.push(b"\x80\xc0\xaf\xed\xa0\x80\xf4\x90\xff\r".to_vec());
+        assert_eq!(parser.next(), Some(Input::Line("\u{fffd}".repeat(9))));
+        // a truncated sequence before a byte that starts again
+        queue.push(b"\xe2\x82a\xf0\x9f\x98\r".to_vec());
+        assert_eq!(parser.next(), Some(Input::Line("\u{fffd}a\u{fffd}".into())));
+        queue.push(b"a\xc3\x03".to_vec());
+        assert_eq!(parser.next(), Some(Input::IncompleteLine("a\u{fffd}".into())));
+        assert_eq!(parser.next(), Some(Input::Control('\x03')));
+        queue.push(b"\xe2\x1b[m".to_vec());
+        assert_eq!(parser.next(), Some(Input::IncompleteLine("\u{fffd}".into())));
+        assert_eq!(
+            parser.next(),
+            Some(Input::EscapeSequence(EscapeSequence::Unknown(b"\x1b[m".into())))
+        );
+    }
+
     #[test]
     fn test_console() {
         let queue = MutexQueue::new();
 
-        let mut console = Console::new(InputParser::new(queue.clone()), Commands::default());
+        let console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let mut console = ConsoleDriver::new(console);
         queue.push(b"\r".to_vec());
-        assert_eq!(console.next(), Some(b"\r\n> ".into()));
+        assert_eq!(console.next(), Some(b"\r\n! ".into()));
         assert_eq!(console.next(), Some(b"unknown command\r\n$ ".into()));
         assert_eq!(console.next(), None);
         queue.push(b"abc\r".to_vec());
-        assert_eq!(console.next(), Some(b"abc\r\n> ".into()));
+        assert_eq!(console.next(), Some(b"abc\r\n! ".into()));
         assert_eq!(console.next(), Some(b"unknown command\r\n$ ".into()));
         assert_eq!(console.next(), None);
         queue.push(b"abc <\r".to_vec());
-        assert_eq!(console.next(), Some(b"abc <\r\n< ".into()));
+        assert_eq!(console.next(), Some(b"abc <\r\n! ".into()));
+        assert_eq!(console.next(), Some(b"unknown command\r\n$ ".into()));
+        assert_eq!(console.next(), None);
+        queue.push(b"cat <\r".to_vec());
+        assert_eq!(console.next(), Some(b"cat <\x1b[?2004h\r\n< ".into()));
         assert_eq!(console.next(), None);
         queue.push(b"plop\r".to_vec());
         assert_eq!(console.next(), Some(b"plop\r\n< ".into()));
+        assert_eq!(console.next(), Some(b"\r\x1b[K> plop\r\n< ".into()));
         assert_eq!(console.next(), None);
         queue.push(b"\x04".to_vec());
-        assert_eq!(console.next(), Some(b"\r\n> ".into()));
-        assert_eq!(console.next(), Some(b"unknown command\r\n$ ".into()));
+        assert_eq!(console.next(), Some(b"\x1b[?2004l\r\n> ".into()));
+        assert_eq!(console.next(), Some(b"\r\n$ ".into()));
         assert_eq!(console.next(), None);
     }
+
+    fn poll_once<F: Future>(f: F) -> Option<F::Output> {
+        let f = core::pin::pin!(f);
+        match f.poll(&mut core::task::Context::from_waker(core::task::Waker::noop())) {
+            Poll::Ready(out) => Some(out),
+            Poll::Pending => None,
+        }
+    }
+
+    #[test]
+    fn test_bracketed_paste() {
+        let queue = MutexQueue::new();
+        let mut parser = InputParser::new(queue.clone());
+        queue.push(b"\x1b[200~a\x04b\r\nc\x1b[Ad\x1b[201~\x04".to_vec());
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::EscapeSequence(EscapeSequence::PasteStart)));
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::Line("a\x04b".into())));
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::IncompleteLine("c".into())));
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::IncompleteLine("\x1b[A".into())));
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::IncompleteLine("d".into())));
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::EscapeSequence(EscapeSequence::PasteEnd)));
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::Control('\x04')));
+        assert_eq!(poll_once(parser.next_wait()), None);
+    }
+
+    #[test]
+    fn test_frame_parser() {
+        let queue = MutexQueue::new();
+        let mut parser = InputParser::new(queue.clone());
+        // bytes stay those of a terminal until the console accepts the handshake
+        queue.push([HANDSHAKE, b"a\r"].concat());
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::EscapeSequence(EscapeSequence::FramedMode)));
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::Line("a".into())));
+        parser.start_frames();
+        let quit = Request::Quit.encode();
+        let (start, end) = quit.split_at(1);
+        queue.push(start.to_vec());
+        assert_eq!(poll_once(parser.next_wait()), None);
+        queue.push(end.to_vec());
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::Frame(b"q".to_vec())));
+        queue.push(b"b\r".to_vec());
+        assert_eq!(poll_once(parser.next_wait()), Some(Input::Line("b".into())));
+    }
+
+    #[test]
+    fn test_heredoc() {
+        let queue = MutexQueue::new();
+        let mut console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"cat <<END\r".to_vec());
+        assert_eq!(next(), Some(b"cat <<END\x1b[?2004h\r\n< ".to_vec()));
+        queue.push(b"a\x04\r".to_vec());
+        assert_eq!(next(), Some(b"a".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"\r\n< ".to_vec()));
+        assert_eq!(next(), Some(b"\r\x1b[K> a\r\n< ".to_vec()));
+        queue.push(b"END\r".to_vec());
+        assert_eq!(next(), Some(b"END\x1b[?2004l\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        assert_eq!(next(), None);
+    }
+
+    #[test]
+    fn test_streaming_pipeline() {
+        let queue = MutexQueue::new();
+        let mut console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"cat < | head -n 1\r".to_vec());
+        assert_eq!(next(), Some(b"cat < | head -n 1\x1b[?2004h\r\n< ".to_vec()));
+        queue.push(b"x\r".to_vec());
+        assert_eq!(next(), Some(b"x\r\n< ".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"\r\x1b[K> x\r\n< ".to_vec()));
+        // head does not need more input: cat is stopped and the input ends
+        assert_eq!(next(), Some(b"\x1b[?2004l\r\n$ ".to_vec()));
+        assert_eq!(next(), None);
+    }
+
+    /// Pending for a number of polls, then finished
+    struct Slow(usize);
+
+    impl RunningCommand for Slow {
+        fn next(&mut self) -> Pin<Box<dyn Future<Output = Option<Output>> + Send + '_>> {
+            Box::pin(poll_fn(|_| match self.0.checked_sub(1) {
+                Some(left) => {
+                    self.0 = left;
+                    Poll::Pending
+                }
+                None => Poll::Ready(None),
+            }))
+        }
+    }
+
+    /// Pending for 3 polls, or for the number of its argument
+    struct SlowCommand;
+
+    impl Command for SlowCommand {
+        fn exec(&self, args: Args) -> Box<dyn RunningCommand> {
+            Box::new(Slow(args.get(0).and_then(|polls| polls.parse().ok()).unwrap_or(3)))
+        }
+    }
+
+    #[test]
+    fn test_type_ahead() {
+        let queue = MutexQueue::new();
+        let mut commands = Commands::new();
+        commands.add("slow", TEST_HELP, SlowCommand);
+        let mut console = Console::new(InputParser::new(queue.clone()), commands, ConsoleConfig::default());
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"slow\r".to_vec());
+        assert_eq!(next(), Some(b"slow\r\n> ".to_vec()));
+        queue.push(b"zy".to_vec());
+        assert_eq!(next(), Some(b"\r\n+ zy".to_vec()));
+        queue.push(b"\x7f".to_vec());
+        assert_eq!(next(), Some(b"\x08\x1b[K".to_vec()));
+        queue.push(b"w\r".to_vec());
+        assert_eq!(next(), Some(b"w".to_vec()));
+        // the type-ahead line is given to the prompt, but not run
+        assert_eq!(next(), Some(b"\r\x1b[K$ zw".to_vec()));
+        queue.push(b"\r".to_vec());
+        assert_eq!(next(), Some(b"\r\n! ".to_vec()));
+        assert_eq!(next(), Some(b"unknown command\r\n$ ".to_vec()));
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut commands = Commands::new();
+        commands.add("slow", TEST_HELP, SlowCommand);
+        let commands = Rc::new(commands);
+        let (queue1, queue2) = (MutexQueue::new(), MutexQueue::new());
+        let console1 = Console::new(InputParser::new(queue1.clone()), commands.clone(), ConsoleConfig::default());
+        let config2 = ConsoleConfig { prompt: "% ".into(), ..ConsoleConfig::default() };
+        let console2 = Console::new(InputParser::new(queue2.clone()), commands.clone(), config2);
+        let (mut session1, mut session2) = (ConsoleDriver::new(console1), ConsoleDriver::new(console2));
+        queue1.push(b"slow\r".to_vec());
+        assert_eq!(session1.next(), Some(b"slow\r\n> ".to_vec()));
+        assert_eq!(session1.next(), None);
+        // the other session runs its own command lines while the first one waits
+        queue2.push(b"set A=2\rslow\r".to_vec());
+        assert_eq!(session2.run(), b"set A=2\r\n> \r\n% slow\r\n> ");
+        let mut out = Vec::new();
+        while !out.ends_with(b"\r\n$ ") {
+            out.extend(session1.run());
+        }
+        queue1.push(b"echo [$A]\r".to_vec());
+        out.extend(session1.run());
+        assert_eq!(out, b"\r\n$ echo [$A]\r\n> \r\n> []\r\n$ ");
+        assert_eq!(Rc::strong_count(&commands), 3);
+    }
+
+    #[test]
+    fn test_jobs() {
+        let queue = MutexQueue::new();
+        let mut commands = Commands::new();
+        commands.add("slow", TEST_HELP, SlowCommand);
+        commands.add("fail", TEST_HELP, FailCommand);
+        let console = Console::new(InputParser::new(queue.clone()), commands, ConsoleConfig::default());
+        let mut driver = ConsoleDriver::new(console);
+        // a few polls, for the jobs to go on
+        let mut type_line = |line: &str| {
+            queue.push(line.as_bytes().to_vec());
+            let out: Vec<u8> = (0..10).flat_map(|_| driver.run()).collect();
+            String::from_utf8(out).unwrap()
+        };
+        let steps = [
+            ("fail &\r", "fail &\r\n> \r\n> [1]\r\n$ \r\x1b[K! [1] oops\r\n$ \r\x1b[K> [1] exit 3  fail\r\n$ "),
+            ("cat < @%1\r", "cat < @%1\r\n> \r\n> a\r\n$ "),
+            ("slow 1000 &\r", "slow 1000 &\r\n> \r\n> [1]\r\n$ "),
+            ("slow 2 > out &\r", "slow 2 > out &\r\n> \r\n> [2]\r\n$ \r\x1b[K> [2] done  slow 2 > out\r\n$ "),
+            ("jobs\r", "jobs\r\n> \r\n> [1] running  slow 1000\r\n$ "),
+            ("kill %1\r", "kill %1\r\n> \r\n> [1] killed  slow 1000\r\n$ "),
+            // typed before the job goes on
+            ("fail &\rwait %1\r", "fail &\r\n> \r\n> [1]\r\n$ wait %1\r\n> \r\n! [1] oops\r\n> [1] exit 3  fail\r\n$ "),
+            ("echo $?\r", "echo $?\r\n> \r\n> 3\r\n$ "),
+            ("slow 8 &\rwait\r", "slow 8 &\r\n> \r\n> [1]\r\n$ wait\r\n> \r\n> [1] done  slow 8\r\n$ "),
+            ("fail &\rfg\r", "fail &\r\n> \r\n> [1]\r\n$ fg\r\n> \r\n> a\r\n! oops\r\n$ "),
+            ("slow 1000 &\r", "slow 1000 &\r\n> \r\n> [1]\r\n$ "),
+            ("fg\r", "fg\r\n> "),
+            ("\x03", "^C\r\n$ "),
+            ("jobs\r", "jobs\r\n> \r\n$ "),
+            ("fg\r", "fg\r\n! no job\r\n$ "),
+            ("fg | head\r", "fg | head\r\n! fg cannot be in a pipeline\r\n$ "),
+            ("cat < &\r", "cat < &\r\n! a job cannot read the console\r\n$ "),
+            ("wait\r", "wait\r\n> \r\n$ "),
+        ];
+        for (line, expected) in steps {
+            assert_eq!(type_line(line), expected, "{:?}", line);
+        }
+    }
+
+    /// Output known in advance, with error lines and an exit status
+    struct Outputs(VecDeque<Output>);
+
+    impl SyncRunningCommand for Outputs {
+        fn next_sync(&mut self) -> Option<Output> {
+            self.0.pop_front()
+        }
+    }
+
+    struct FailCommand;
+
+    impl Command for FailCommand {
+        fn exec(&self, _args: Args) -> Box<dyn RunningCommand> {
+            Box::new(Outputs([Output::Line("a".into()), Output::Error("oops".into()), Output::Exit(3)].into()))
+        }
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let queue = MutexQueue::new();
+        let mut commands = Commands::new();
+        commands.add("fail", TEST_HELP, FailCommand);
+        let mut console = Console::new(InputParser::new(queue.clone()), commands, ConsoleConfig::default());
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"fail\r".to_vec());
+        assert_eq!(next(), Some(b"fail\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> a".to_vec()));
+        assert_eq!(next(), Some(b"\r\n! oops".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"echo $?\r".to_vec());
+        assert_eq!(next(), Some(b"echo $?\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> 3".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        // errors are not piped, the status is the one of the last command
+        queue.push(b"fail | grep a > out\r".to_vec());
+        assert_eq!(next(), Some(b"fail | grep a > out\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"\r\n! oops".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"echo \"[$?]\"\r".to_vec());
+        assert_eq!(next(), Some(b"echo \"[$?]\"\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> [0]".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"aoc\r".to_vec());
+        assert_eq!(next(), Some(b"aoc\r\n! ".to_vec()));
+        assert_eq!(next(), Some(b"unknown command\r\n$ ".to_vec()));
+        queue.push(b"echo $?\r".to_vec());
+        assert_eq!(next(), Some(b"echo $?\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> 1".to_vec()));
+    }
+
+    #[test]
+    fn test_run_script() {
+        let queue = MutexQueue::new();
+        let mut commands = Commands::new();
+        commands.add("fail", TEST_HELP, FailCommand);
+        let mut console = Console::new(InputParser::new(queue.clone()), commands, ConsoleConfig::default());
+        console.run_script(b"# count lines\nload in <<END\nab\n\nEND\ncat < @in | head -n 1\nfail\necho not run\n");
+        let mut output = Vec::new();
+        while console.scripting() {
+            output.extend(poll_once(console.next_wait()).map(|(a, b)| [a, b].concat()).unwrap());
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "$ load in <<END\r\n? 0\r\n$ cat < @in | head -n 1\r\n> ab\r\n? 0\r\n$ fail\r\n> a\r\n! oops\r\n? 3\r\n"
+        );
+        assert_eq!(console.status(), 3);
+    }
+
+    #[test]
+    fn test_source() {
+        let queue = MutexQueue::new();
+        let mut console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        console.buffers.insert("s".into(), ["echo a".into(), "cat <".into(), "b".into()].into());
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"source @s\r".to_vec());
+        assert_eq!(next(), Some(b"source @s\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n".to_vec()));
+        assert_eq!(next(), Some(b"$ echo a\r\n".to_vec()));
+        assert_eq!(next(), Some(b"> a\r\n".to_vec()));
+        assert_eq!(next(), Some(b"? 0\r\n".to_vec()));
+        assert_eq!(next(), Some(b"$ cat <\r\n".to_vec()));
+        assert_eq!(next(), Some(b"> b\r\n".to_vec()));
+        assert_eq!(next(), Some(b"? 0\r\n".to_vec()));
+        assert_eq!(next(), Some(b"$ ".to_vec()));
+        assert_eq!(next(), None);
+    }
+
+    #[test]
+    fn test_set() {
+        let queue = MutexQueue::new();
+        let mut console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"set prompt \"% \"\r".to_vec());
+        assert_eq!(next(), Some(b"set prompt \"% \"\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n% ".to_vec()));
+        queue.push(b"set color on\r".to_vec());
+        // the setting applies as soon as the line is run
+        assert_eq!(next(), Some(b"\x1b[1mset color on\x1b[0m\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n% ".to_vec()));
+        queue.push(b"x\r".to_vec());
+        assert_eq!(next(), Some(b"\x1b[1mx\x1b[0m\r\n! ".to_vec()));
+        assert_eq!(next(), Some(b"\x1b[31munknown command\x1b[0m\r\n% ".to_vec()));
+        queue.push(b"set echo off\r".to_vec());
+        assert_eq!(next(), Some(b"\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n% ".to_vec()));
+        queue.push(b"set newline\r".to_vec());
+        assert_eq!(next(), Some(b"\r\n! ".to_vec()));
+        assert_eq!(next(), Some(b"\x1b[31musage: set <setting> <value>\x1b[0m\r\n% ".to_vec()));
+    }
+
+    #[test]
+    fn test_variables() {
+        static NOW: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+        let queue = MutexQueue::new();
+        let console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let mut console = console.with_clock(|| NOW.fetch_add(25, core::sync::atomic::Ordering::Relaxed)).with_core_id(|| 0);
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"set D=16\r".to_vec());
+        assert_eq!(next(), Some(b"set D=16\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"echo day $D ${D}b took $DURATION on $CORE$X\r".to_vec());
+        assert_eq!(next(), Some(b"echo day $D ${D}b took $DURATION on $CORE$X\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> day 16 16b took 25 on 0".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"set | grep =\r".to_vec());
+        let mut out = Vec::new();
+        while !out.ends_with(b"\r\n$ ") {
+            out.extend(next().unwrap());
+        }
+        assert_eq!(out, b"set | grep =\r\n> \r\n> D=16\r\n$ ");
+        queue.push(b"set D=\r".to_vec());
+        assert_eq!(next(), Some(b"set D=\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"set CORE=1\r".to_vec());
+        assert_eq!(next(), Some(b"set CORE=1\r\n! ".to_vec()));
+        assert_eq!(next(), Some(b"read-only variable\r\n$ ".to_vec()));
+        queue.push(b"echo [$D]\r".to_vec());
+        assert_eq!(next(), Some(b"echo [$D]\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> []".to_vec()));
+    }
+
+    #[test]
+    fn test_pager_timer() {
+        use core::sync::atomic::{AtomicU64, Ordering};
+        static NOW: AtomicU64 = AtomicU64::new(1000);
+        static DEADLINE: AtomicU64 = AtomicU64::new(0);
+        let queue = MutexQueue::new();
+        let console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let console = console.with_clock(|| NOW.load(Ordering::Relaxed));
+        let console = console.with_timer(|deadline, _| DEADLINE.store(deadline, Ordering::Relaxed));
+        let mut driver = ConsoleDriver::new(console);
+        queue.push(b"echo a | more\r".to_vec());
+        // the commands wait for the terminal until the timer wakes the console
+        assert_eq!(driver.run(), [b"echo a | more\r\n> ", config::SIZE_QUERY].concat());
+        assert_eq!(DEADLINE.load(Ordering::Relaxed), 1200);
+        NOW.store(1200, Ordering::Relaxed);
+        assert_eq!(driver.run(), b"\r\n> a\r\n$ ");
+    }
+
+    #[test]
+    fn test_alias() {
+        let queue = MutexQueue::new();
+        let mut console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"alias e = echo [$1] | head -n 1\r".to_vec());
+        assert_eq!(next(), Some(b"alias e = echo [$1] | head -n 1\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"alias hi = echo hi\r".to_vec());
+        assert_eq!(next(), Some(b"alias hi = echo hi\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"e 'a b' c\r".to_vec());
+        let mut out = Vec::new();
+        while !out.ends_with(b"\r\n$ ") {
+            out.extend(next().unwrap());
+        }
+        assert_eq!(out, b"e 'a b' c\r\n> \r\n> [a b]\r\n$ ");
+        queue.push(b"hi there\r".to_vec());
+        assert_eq!(next(), Some(b"hi there\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> hi there".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"alias\r".to_vec());
+        assert_eq!(next(), Some(b"alias\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> e = echo [$1] | head -n 1".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> hi = echo hi".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"alias | grep =\r".to_vec());
+        let mut out = Vec::new();
+        while !out.ends_with(b"\r\n$ ") {
+            out.extend(next().unwrap());
+        }
+        assert_eq!(out, b"alias | grep =\r\n> \r\n> e = echo [$1] | head -n 1\r\n> hi = echo hi\r\n$ ");
+        queue.push(b"unalias hi\r".to_vec());
+        assert_eq!(next(), Some(b"unalias hi\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"alias hi\r".to_vec());
+        assert_eq!(next(), Some(b"alias hi\r\n! ".to_vec()));
+        assert_eq!(next(), Some(b"no such alias\r\n$ ".to_vec()));
+    }
+
+    #[test]
+    fn test_framed() {
+        let queue = MutexQueue::new();
+        let mut console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        let run = |name: &str, args: &[&str], input: &[u8]| {
+            let args = args.iter().map(|&arg| arg.into()).collect();
+            Request::Run { name: name.into(), args, input: input.to_vec() }.encode()
+        };
+        queue.push(HANDSHAKE.to_vec());
+        assert_eq!(next(), Some([b"\0".as_slice(), &Response::READY.encode()].concat()));
+        queue.push(run("echo", &["a b"], b""));
+        assert_eq!(next(), Some(Vec::new()));
+        assert_eq!(next(), Some(Response::Line("a b").encode()));
+        assert_eq!(next(), Some(Response::Status(0).encode()));
+        queue.push(run("cat", &[], b"1\n\n2"));
+        assert_eq!(next(), Some(Vec::new()));
+        assert_eq!(next(), Some(Response::Line("1").encode()));
+        assert_eq!(next(), Some(Response::Line("").encode()));
+        assert_eq!(next(), Some(Response::Line("2").encode()));
+        assert_eq!(next(), Some(Response::Status(0).encode()));
+        queue.push(run("xyzzy", &[], b""));
+        assert_eq!(next(), Some([Response::Error("unknown command").encode(), Response::Status(1).encode()].concat()));
+        queue.push(b"\x02x\0".to_vec());
+        assert_eq!(next(), Some([Response::Error("bad request").encode(), Response::Status(1).encode()].concat()));
+        queue.push(Request::Cancel.encode());
+        assert_eq!(next(), Some(Response::Status(1).encode()));
+        queue.push(Request::Quit.encode());
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        queue.push(b"echo ok\r".to_vec());
+        assert_eq!(next(), Some(b"echo ok\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> ok".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+        // a handshake typed while a command reads its input is not one
+        queue.push([b"cat <\r", HANDSHAKE, b"x\r\x04"].concat());
+        assert_eq!(next(), Some(b"cat <\x1b[?2004h\r\n< ".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"x\r\n< ".to_vec()));
+        assert_eq!(next(), Some(b"\r\x1b[K> x\r\n< ".to_vec()));
+        assert_eq!(next(), Some(b"\x1b[?2004l\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n$ ".to_vec()));
+    }
+
+    #[test]
+    fn test_input_limit() {
+        let queue = MutexQueue::new();
+        let config = ConsoleConfig { input_limit: Some(4), ..ConsoleConfig::default() };
+        let mut console = Console::new(InputParser::new(queue.clone()), Commands::default(), config);
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"cat <\r".to_vec());
+        assert_eq!(next(), Some(b"cat <\x1b[?2004h\r\n< ".to_vec()));
+        queue.push(b"\x1b[200~ab\rcde\recho no\r\x1b[201~".to_vec());
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"ab\r\n< ".to_vec()));
+        assert_eq!(next(), Some(b"\r\x1b[K> ab\r\n< ".to_vec()));
+        assert_eq!(next(), Some(b"cde\x1b[?2004l\r\n! ".to_vec()));
+        assert_eq!(next(), Some(b"input over 4 bytes, command stopped\r\n$ ".to_vec()));
+        // the rest of the paste is dropped
+        assert_eq!(next(), Some(b"".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        queue.push(b"echo $?\r".to_vec());
+        assert_eq!(next(), Some(b"echo $?\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> 1".to_vec()));
+    }
+
+    #[test]
+    fn test_input_heap() {
+        let queue = MutexQueue::new();
+        let console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default());
+        let mut console = console.with_heap_stats(|| HeapStats { used: 0, free: HEAP_RESERVE + 2 });
+        let mut next = || poll_once(console.next_wait()).map(|(a, b)| [a, b].concat());
+        queue.push(b"load x <\r".to_vec());
+        assert_eq!(next(), Some(b"load x <\x1b[?2004h\r\n< ".to_vec()));
+        queue.push(b"ab\r".to_vec());
+        assert_eq!(next(), Some(b"ab\r\n< ".to_vec()));
+        assert_eq!(next(), Some(b"".to_vec()));
+        queue.push(b"abc".to_vec());
+        assert_eq!(next(), Some(b"abc\x1b[?2004l\r\n! ".to_vec()));
+        assert_eq!(next(), Some(b"out of memory for input, command stopped\r\n$ ".to_vec()));
+        queue.push(b"ls\r".to_vec());
+        assert_eq!(next(), Some(b"ls\r\n> ".to_vec()));
+        assert_eq!(next(), Some(b"\r\n> heap: 0 bytes used, 16386 bytes free".to_vec()));
+    }
+
+    fn parse_escape(bytes: &[u8]) -> EscapeSequence {
+        let mut acc = ParserAccumulator::new(false);
+        for &b in bytes {
+            match acc.advance(b) {
+                Ok(Input::EscapeSequence(seq)) => return seq,
+                Ok(other) => panic!("unexpected input {:?}", other),
+                Err(next) => acc = next,
+            }
+        }
+        panic!("incomplete escape sequence");
+    }
+
+    fn key(key: Key) -> EscapeSequence {
+        EscapeSequence::Key(key, Modifiers::default())
+    }
+
+    #[test]
+    fn test_escape_sequence_xterm() {
+        assert_eq!(parse_escape(b"\x1b[A"), key(Key::Up));
+        assert_eq!(parse_escape(b"\x1b[D"), key(Key::Left));
+        assert_eq!(parse_escape(b"\x1b[H"), key(Key::Home));
+        assert_eq!(parse_escape(b"\x1b[F"), key(Key::End));
+        assert_eq!(parse_escape(b"\x1b[2~"), key(Key::Insert));
+        assert_eq!(parse_escape(b"\x1b[3~"), key(Key::Delete));
+        assert_eq!(parse_escape(b"\x1b[5~"), key(Key::PageUp));
+        assert_eq!(parse_escape(b"\x1b[6~"), key(Key::PageDown));
+        assert_eq!(parse_escape(b"\x1bOP"), key(Key::F(1)));
+        assert_eq!(parse_escape(b"\x1b[15~"), key(Key::F(5)));
+        assert_eq!(parse_escape(b"\x1b[21~"), key(Key::F(10)));
+        assert_eq!(parse_escape(b"\x1b[24~"), key(Key::F(12)));
+        assert_eq!(
+            parse_escape(b"\x1b[1;5C"),
+            EscapeSequence::Key(Key::Right, Modifiers { ctrl: true, ..Default::default() })
+        );
+        assert_eq!(
+            parse_escape(b"\x1b[3;4~"),
+            EscapeSequence::Key(Key::Delete, Modifiers { shift: true, alt: true, ..Default::default() })
+        );
+        assert_eq!(
+            parse_escape(b"\x1b[1;2P"),
+            EscapeSequence::Key(Key::F(1), Modifiers { shift: true, ..Default::default() })
+        );
+        assert_eq!(parse_escape(b"\x1b[200~"), EscapeSequence::PasteStart);
+        assert_eq!(parse_escape(b"\x1b[201~"), EscapeSequence::PasteEnd);
+        assert_eq!(
+            parse_escape(b"\x1b[24;80R"),
+            EscapeSequence::CursorPosition { row: 24, col: 80 }
+        );
+        assert_eq!(
+            parse_escape(b"\x1b[8;40;132t"),
+            EscapeSequence::TextAreaSize { rows: 40, cols: 132 }
+        );
+        assert_eq!(
+            parse_escape(b"\x1b[?1;2c"),
+            EscapeSequence::Unknown(b"\x1b[?1;2c".into())
+        );
+    }
+
+    #[test]
+    fn test_escape_sequence_vt100() {
+        assert_eq!(parse_escape(b"\x1bOA"), key(Key::Up));
+        assert_eq!(parse_escape(b"\x1bOB"), key(Key::Down));
+        assert_eq!(parse_escape(b"\x1bOC"), key(Key::Right));
+        assert_eq!(parse_escape(b"\x1bOD"), key(Key::Left));
+        assert_eq!(parse_escape(b"\x1bOH"), key(Key::Home));
+        assert_eq!(parse_escape(b"\x1bOF"), key(Key::End));
+        assert_eq!(parse_escape(b"\x1bOS"), key(Key::F(4)));
+    }
+
+    #[test]
+    fn test_escape_sequence_putty() {
+        assert_eq!(parse_escape(b"\x1b[1~"), key(Key::Home));
+        assert_eq!(parse_escape(b"\x1b[4~"), key(Key::End));
+        assert_eq!(parse_escape(b"\x1b[11~"), key(Key::F(1)));
+        assert_eq!(parse_escape(b"\x1b[14~"), key(Key::F(4)));
+        assert_eq!(parse_escape(b"\x1b[[A"), key(Key::F(1)));
+        assert_eq!(parse_escape(b"\x1b[[E"), key(Key::F(5)));
+    }
+
+    #[derive(Clone)]
+    struct DaysCommand;
+
+    impl SyncCommand for DaysCommand {
+        type RunningCommand = Lines;
+        fn exec_sync(&self, _args: Args, _input: InputLines) -> Self::RunningCommand {
+            Lines::from(Vec::new())
+        }
+
+        fn complete(&self, args: &[&str], prefix: &str) -> Vec<String> {
+            match args {
+                [] => ["1", "10", "11", "2"]
+                    .iter()
+                    .filter(|d| d.starts_with(prefix))
+                    .map(ToString::to_string)
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut commands = Commands::new();
+        commands.add("aoc", TEST_HELP, DaysCommand);
+        commands.add("hist", TEST_HELP, DaysCommand);
+        let console = Console::new((), commands, ConsoleConfig::default());
+        assert_eq!(console.complete("a"), ["alias", "aoc"]);
+        assert_eq!(console.complete("hi"), ["history", "hist"]);
+        assert_eq!(console.complete("aoc 1"), ["1", "10", "11"]);
+        assert_eq!(console.complete("aoc 1 "), Vec::<String>::new());
+        assert_eq!(console.complete("foo "), Vec::<String>::new());
+        assert_eq!(common_prefix(&console.complete("hi")), "hist");
+        assert_eq!(common_prefix(&console.complete("aoc 1")), "1");
+        assert_eq!(common_prefix(&[]), "");
+        assert_eq!(console.complete("help h"), ["help", "history", "head", "hist"]);
+        let mut console = console;
+        console.buffers.insert("day6".into(), Vec::new());
+        console.buffers.insert("out".into(), Vec::new());
+        assert_eq!(console.complete("aoc 6 < @d"), ["@day6"]);
+        assert_eq!(console.complete("rm "), ["day6", "out"]);
+        assert_eq!(console.complete("set new"), ["newline"]);
+        assert_eq!(console.complete("set newline "), ["crlf", "lf"]);
+        console.config.aliases.insert("d".into(), "aoc $1 <".into());
+        assert_eq!(console.complete("d"), ["d"]);
+        assert_eq!(console.complete("unalias "), ["d"]);
+    }
+
+    const TEST_HELP: CommandHelp = CommandHelp {
+        summary: "run a day",
+        usage: "aoc <day> [<]",
+        args: &[("<day>", "day number"), ("<", "read input")],
+    };
+
+    #[test]
+    fn test_help() {
+        let mut commands = Commands::new();
+        commands.add("aoc", TEST_HELP, DaysCommand);
+        let console = Console::new((), commands, ConsoleConfig::default());
+        assert_eq!(
+            console.help(None),
+            Ok(vec![
+                "help     list commands, or show how to use one".into(),
+                "history  list previous command lines".into(),
+                "echo     output its arguments".into(),
+                "cat      output input lines".into(),
+                "load     save input lines in a buffer".into(),
+                "ls       list buffers with their size".into(),
+                "rm       delete buffers".into(),
+                "grep     keep input lines containing a text".into(),
+                "head     keep the first input lines".into(),
+                "more     show input lines a screenful at a time, at the end of a command line".into(),
+                "source   run the command lines of a buffer, until one fails".into(),
+                "set      show or change how the console looks".into(),
+                "resize   ask the terminal for its size, read with $ROWS and $COLUMNS".into(),
+                "alias    list or define command names replaced by a command line".into(),
+                "unalias  delete aliases".into(),
+                "jobs     list command lines started with &, whose output goes to buffer %<job>".into(),
+                "fg       show the rest of the output of a job, until it finishes".into(),
+                "wait     wait for a job to finish, or for all of them".into(),
+                "kill     stop jobs".into(),
+                "aoc      run a day".into(),
+            ])
+        );
+        assert_eq!(
+            console.help(Some("aoc")),
+            Ok(vec![
+                "usage: aoc <day> [<]".into(),
+                "run a day".into(),
+                "  <day>  day number".into(),
+                "  <      read input".into(),
+            ])
+        );
+        assert_eq!(console.help(Some("aco")), Err("unknown command, did you mean aoc?".into()));
+        assert_eq!(console.help(Some("xyzzy")), Err("unknown command".into()));
+        assert_eq!(distance("histroy", "history"), 2);
+        assert_eq!(distance("", "aoc"), 3);
+    }
 }
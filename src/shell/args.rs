@@ -0,0 +1,478 @@
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::iter::Peekable;
+use core::str::Chars;
+use super::TerminalSize;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Token {
+    Word(String),
+    /// `<`: read input lines from the console
+    Input,
+    /// `|`: output lines of the previous command are the input of the next one
+    Pipe,
+    /// `>`: save output lines in a buffer
+    Output,
+    /// `<<`: read input lines from the console until a terminator line
+    Heredoc,
+    /// `&`: run the command line in the background
+    Background,
+}
+
+/// Value of a variable expanded in a command line, such as `?` for the last exit status, `None` when not set
+pub type Variables<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// Command line templates by name, `$1` to `$9` being the words following the name
+pub type Aliases = BTreeMap<String, String>;
+
+/// Split a command line on runs of whitespace, handling quotes, backslash escapes, variables and operators
+pub fn tokenize(line: &str, vars: Variables) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {
+                if let Some(w) = word.take() {
+                    tokens.push(Token::Word(w));
+                }
+            }
+            '<' | '|' | '>' | '&' => {
+                if let Some(w) = word.take() {
+                    tokens.push(Token::Word(w));
+                }
+                tokens.push(match c {
+                    '<' if chars.next_if_eq(&'<').is_some() => Token::Heredoc,
+                    '<' => Token::Input,
+                    '|' => Token::Pipe,
+                    '&' => Token::Background,
+                    _ => Token::Output,
+                });
+            }
+            '\'' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => w.push(c),
+                        None => return Err("unterminated quote"),
+                    }
+                }
+            }
+            '"' => {
+                let w = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$')) => w.push(c),
+                            Some(c) => {
+                                w.push('\\');
+                                w.push(c);
+                            }
+                            None => return Err("unterminated quote"),
+                        },
+                        Some('$') => expand(&mut chars, vars, w)?,
+                        Some(c) => w.push(c),
+                        None => return Err("unterminated quote"),
+                    }
+                }
+            }
+            '$' => expand(&mut chars, vars, word.get_or_insert_with(String::new))?,
+            '\\' => {
+                let w = word.get_or_insert_with(String::new);
+                w.push(chars.next().unwrap_or('\\'));
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(w) = word {
+        tokens.push(Token::Word(w));
+    }
+    Ok(tokens)
+}
+
+/// Replace `$?`, `$1`, `$NAME` or `${NAME}` by its value, empty when not set, a `$` followed by anything else being kept
+fn expand(chars: &mut Peekable<Chars>, vars: Variables, word: &mut String) -> Result<(), &'static str> {
+    let name = match chars.peek() {
+        Some(c) if *c == '?' || c.is_ascii_digit() => chars.next().into_iter().collect(),
+        Some('{') => {
+            chars.next();
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => return Err("unterminated variable"),
+                }
+            }
+            if name != "?" && !is_name(&name) {
+                return Err("bad variable name");
+            }
+            name
+        }
+        Some(&c) if c.is_ascii_alphabetic() || c == '_' => {
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_') {
+                name.push(c);
+            }
+            name
+        }
+        _ => {
+            word.push('$');
+            return Ok(());
+        }
+    };
+    word.push_str(&vars(&name).unwrap_or_default());
+    Ok(())
+}
+
+/// Letters, digits and `_`, not starting with a digit
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Tokens of an alias template, with its arguments as `$1` to `$9`, or after it when it uses none
+fn expand_alias(template: &str, args: Vec<String>, vars: Variables) -> Result<Vec<Token>, &'static str> {
+    let positional = Cell::new(false);
+    let mut tokens = tokenize(template, &|name| match name.parse::<usize>() {
+        Ok(idx) => {
+            positional.set(true);
+            args.get(idx.checked_sub(1)?).cloned()
+        }
+        Err(_) => vars(name),
+    })?;
+    if !positional.get() {
+        tokens.extend(args.into_iter().map(Token::Word));
+    }
+    Ok(tokens)
+}
+
+/// Name and template of `alias name = template`, the template being kept as typed
+///
+/// Only a name goes before `=`, other command lines with `alias` being parsed as any other.
+pub fn alias_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix("alias")?;
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let (name, template) = rest.split_once('=')?;
+    let name = name.trim();
+    is_name(name).then_some((name, template.trim()))
+}
+
+/// Tokens of a command line, after the ones of the alias being expanded
+struct TokenStream {
+    alias: VecDeque<Token>,
+    line: Peekable<alloc::vec::IntoIter<Token>>,
+}
+
+impl TokenStream {
+    fn next(&mut self) -> Option<Token> {
+        self.alias.pop_front().or_else(|| self.line.next())
+    }
+
+    fn next_if(&mut self, f: impl Fn(&Token) -> bool) -> Option<Token> {
+        match self.alias.front() {
+            Some(token) if f(token) => self.alias.pop_front(),
+            Some(_) => None,
+            None => self.line.next_if(f),
+        }
+    }
+
+    /// Words following the name of an alias, up to an operator
+    fn alias_args(&mut self) -> Vec<String> {
+        let mut args = Vec::new();
+        while let Some(Token::Word(word)) = self.line.next_if(|t| matches!(t, Token::Word(_))) {
+            args.push(word);
+        }
+        args
+    }
+}
+
+/// Name and arguments of one command of a pipeline
+pub struct SimpleCommand {
+    pub name: String,
+    pub args: Args,
+}
+
+impl SimpleCommand {
+    fn from_words(words: Vec<String>) -> Self {
+        let mut words = words.into_iter();
+        let name = words.next().unwrap_or_default();
+        Self {
+            name,
+            args: words.collect(),
+        }
+    }
+}
+
+/// Where the input lines of the first command come from
+#[derive(Eq, PartialEq, Debug)]
+pub enum InputFrom {
+    Nothing,
+    /// `<`: typed or pasted in the console until Ctrl-D
+    Console,
+    /// `< @name`: lines saved in a buffer
+    Buffer(String),
+    /// `<<TERM`: typed or pasted in the console until a line equal to the terminator
+    Heredoc(String),
+}
+
+/// Command line once tokenized
+pub struct CommandLine {
+    pub pipeline: Vec<SimpleCommand>,
+    pub input: InputFrom,
+    /// `> name`: buffer receiving the output lines instead of the console
+    pub output: Option<String>,
+    /// `&`: started as a job, the console prompting again right away
+    pub background: bool,
+}
+
+impl CommandLine {
+    /// Command line of `line`, a command name typed as an alias being replaced by its template
+    pub fn parse(line: &str, vars: Variables, aliases: &Aliases) -> Result<Self, &'static str> {
+        let mut pipeline = Vec::new();
+        let mut words = Vec::new();
+        let mut input = InputFrom::Nothing;
+        let mut output = None;
+        let mut background = false;
+        let mut tokens = TokenStream {
+            alias: VecDeque::new(),
+            line: tokenize(line, vars)?.into_iter().peekable(),
+        };
+        loop {
+            // the words of an alias are not expanded again, so that it can be named after its command
+            let typed = tokens.alias.is_empty();
+            let Some(token) = tokens.next() else { break };
+            match token {
+                Token::Word(word) if typed && words.is_empty() && aliases.contains_key(&word) => {
+                    let args = tokens.alias_args();
+                    tokens.alias = expand_alias(&aliases[&word], args, vars)?.into();
+                }
+                Token::Word(word) => words.push(word),
+                Token::Input => {
+                    let buffer = tokens.next_if(|t| matches!(t, Token::Word(w) if w.starts_with('@')));
+                    input = match buffer {
+                        Some(Token::Word(name)) => InputFrom::Buffer(name[1..].into()),
+                        _ => InputFrom::Console,
+                    };
+                }
+                Token::Heredoc => match tokens.next() {
+                    Some(Token::Word(terminator)) => input = InputFrom::Heredoc(terminator),
+                    _ => return Err("missing heredoc terminator"),
+                },
+                Token::Output => match tokens.next() {
+                    Some(Token::Word(name)) => output = Some(name.strip_prefix('@').unwrap_or(&name).into()),
+                    _ => return Err("missing buffer name"),
+                },
+                Token::Background => match tokens.next() {
+                    None => background = true,
+                    Some(_) => return Err("& only ends a command line"),
+                },
+                Token::Pipe if words.is_empty() => return Err("empty command in pipeline"),
+                Token::Pipe => pipeline.push(SimpleCommand::from_words(core::mem::take(&mut words))),
+            }
+        }
+        if words.is_empty() && !pipeline.is_empty() {
+            return Err("empty command in pipeline");
+        }
+        pipeline.push(SimpleCommand::from_words(words));
+        Ok(Self {
+            pipeline,
+            input,
+            output,
+            background,
+        })
+    }
+}
+
+/// Arguments of a command, options being separated from positional arguments
+///
+/// `--flag` and `--key=value` are long options, `-k value` a short one, and `--` ends options.
+#[derive(Eq, PartialEq, Debug, Default, Clone)]
+pub struct Args {
+    pub positional: Vec<String>,
+    pub options: Vec<(String, Option<String>)>,
+    /// of the console running the command, for output fitting it
+    pub terminal: TerminalSize,
+}
+
+impl Args {
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.positional.get(idx).map(String::as_str)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find_map(|(n, v)| (n == name).then_some(v.as_deref()).flatten())
+    }
+}
+
+impl FromIterator<String> for Args {
+    fn from_iter<T: IntoIterator<Item = String>>(iter: T) -> Self {
+        let mut args = Args::default();
+        let mut iter = iter.into_iter().peekable();
+        while let Some(word) = iter.next() {
+            if word == "--" {
+                args.positional.extend(iter);
+                break;
+            } else if let Some(long) = word.strip_prefix("--") {
+                let option = match long.split_once('=') {
+                    Some((key, value)) => (key.into(), Some(value.into())),
+                    None => (long.into(), None),
+                };
+                args.options.push(option);
+            } else if let Some(short) = word
+                .strip_prefix('-')
+                .filter(|s| s.starts_with(|c: char| c.is_ascii_alphabetic()))
+            {
+                let value = iter.next_if(|next| !next.starts_with('-'));
+                args.options.push((short.into(), value));
+            } else {
+                args.positional.push(word);
+            }
+        }
+        args
+    }
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn no_vars(_: &str) -> Option<String> {
+        None
+    }
+
+    fn words(line: &str) -> Vec<Token> {
+        tokenize(line, &no_vars).unwrap()
+    }
+
+    fn parse(line: &str) -> Result<CommandLine, &'static str> {
+        CommandLine::parse(line, &no_vars, &Aliases::new())
+    }
+
+    fn word(s: &str) -> Token {
+        Token::Word(s.to_string())
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(words(""), vec![]);
+        assert_eq!(words("  aoc   16  "), vec![word("aoc"), word("16")]);
+        assert_eq!(words("aoc 16<"), vec![word("aoc"), word("16"), Token::Input]);
+        assert_eq!(words("echo 'a  b' \"c \\\" d\""), vec![word("echo"), word("a  b"), word("c \" d")]);
+        assert_eq!(words("echo a\\ b '<' \\<"), vec![word("echo"), word("a b"), word("<"), word("<")]);
+        assert_eq!(words("echo ''"), vec![word("echo"), word("")]);
+        assert_eq!(words("echo \"\\n\""), vec![word("echo"), word("\\n")]);
+        assert_eq!(words("aoc 6 <|grep '|'"), vec![word("aoc"), word("6"), Token::Input, Token::Pipe, word("grep"), word("|")]);
+        assert_eq!(tokenize("echo 'abc", &no_vars), Err("unterminated quote"));
+        assert_eq!(tokenize("echo \"abc", &no_vars), Err("unterminated quote"));
+        let status = |name: &str| (name == "?").then(|| "1".to_string());
+        assert_eq!(
+            tokenize("echo $? \"[$?]\" '$?' \\$? $", &status),
+            Ok(vec![word("echo"), word("1"), word("[1]"), word("$?"), word("$?"), word("$")])
+        );
+        let vars = |name: &str| (name == "D").then(|| "16".to_string());
+        assert_eq!(
+            tokenize("aoc $D ${D}a \"$D\" $Da $1 ${?}", &vars),
+            Ok(vec![word("aoc"), word("16"), word("16a"), word("16"), word(""), word(""), word("")])
+        );
+        assert_eq!(tokenize("aoc ${D", &vars), Err("unterminated variable"));
+        assert_eq!(tokenize("aoc ${1}", &vars), Err("bad variable name"));
+        assert!(is_name("_day2") && !is_name("2day") && !is_name(""));
+    }
+
+    #[test]
+    fn test_command_line() {
+        let line = parse("aoc 14 < | head -n 3 | grep Part").unwrap();
+        assert_eq!(line.input, InputFrom::Console);
+        assert_eq!(line.output, None);
+        let names: Vec<&str> = line.pipeline.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["aoc", "head", "grep"]);
+        assert_eq!(line.pipeline[1].args.option("n"), Some("3"));
+        assert_eq!(line.pipeline[2].args.get(0), Some("Part"));
+        assert_eq!(parse("").unwrap().pipeline[0].name, "");
+        assert!(parse("aoc 1 |").is_err());
+        assert!(parse("| grep a").is_err());
+        assert!(parse("aoc 1 || grep a").is_err());
+        let line = parse("aoc 6 < @day6 > out").unwrap();
+        assert_eq!(line.input, InputFrom::Buffer("day6".into()));
+        assert_eq!(line.output.as_deref(), Some("out"));
+        assert_eq!(line.pipeline[0].args.positional, ["6"]);
+        let line = parse("load day6 <").unwrap();
+        assert_eq!(line.input, InputFrom::Console);
+        assert_eq!(line.pipeline[0].args.positional, ["day6"]);
+        assert_eq!(parse("aoc 6 >").err(), Some("missing buffer name"));
+        let line = parse("aoc 6 <<EOF").unwrap();
+        assert_eq!(line.input, InputFrom::Heredoc("EOF".into()));
+        assert_eq!(line.pipeline[0].args.positional, ["6"]);
+        assert_eq!(parse("aoc 6 << 'END' | head").unwrap().input, InputFrom::Heredoc("END".into()));
+        assert_eq!(parse("aoc 6 <<").err(), Some("missing heredoc terminator"));
+        let line = parse("aoc 6 < @day6 | head > out &").unwrap();
+        assert!(line.background);
+        assert_eq!(line.output.as_deref(), Some("out"));
+        assert!(!parse("aoc 6 '&'").unwrap().background);
+        assert_eq!(parse("aoc 6 & | head").err(), Some("& only ends a command line"));
+    }
+
+    #[test]
+    fn test_aliases() {
+        let aliases: Aliases = [("d", "aoc $1 <"), ("ls", "ls -l"), ("two", "echo $2 $1 | head")]
+            .into_iter()
+            .map(|(name, template)| (name.into(), template.into()))
+            .collect();
+        let parse = |line| CommandLine::parse(line, &no_vars, &aliases).unwrap();
+        let line = parse("d 16 | grep Part");
+        assert_eq!(line.input, InputFrom::Console);
+        assert_eq!(line.pipeline[0].name, "aoc");
+        assert_eq!(line.pipeline[0].args.positional, ["16"]);
+        assert_eq!(line.pipeline[1].name, "grep");
+        let line = parse("ls -a");
+        assert_eq!(line.pipeline[0].name, "ls");
+        assert!(line.pipeline[0].args.flag("l") && line.pipeline[0].args.flag("a"));
+        let line = parse("two 'a b' c");
+        assert_eq!(line.pipeline[0].args.positional, ["c", "a b"]);
+        assert_eq!(line.pipeline[1].name, "head");
+        assert_eq!(parse("echo d").pipeline[0].args.positional, ["d"]);
+        assert_eq!(alias_definition(" alias d = aoc $1 <"), Some(("d", "aoc $1 <")));
+        assert_eq!(alias_definition("alias d=x"), Some(("d", "x")));
+        assert_eq!(alias_definition("aliases d = x"), None);
+        assert_eq!(alias_definition("alias d"), None);
+        assert_eq!(alias_definition("alias ll = ls | more"), Some(("ll", "ls | more")));
+        assert_eq!(alias_definition("alias | grep ="), None);
+        assert_eq!(alias_definition("alias && echo a=b"), None);
+        let line = parse("alias | grep =");
+        assert_eq!(line.pipeline[0].name, "alias");
+        assert_eq!(line.pipeline[1].args.positional, ["="]);
+    }
+
+    #[test]
+    fn test_args() {
+        let args: Args = ["16", "--verbose", "--part=2", "-n", "3", "-5", "-x", "--", "--raw"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(args.positional, ["16", "-5", "--raw"]);
+        assert_eq!(args.get(0), Some("16"));
+        assert!(args.flag("verbose"));
+        assert!(args.flag("x"));
+        assert!(!args.flag("raw"));
+        assert_eq!(args.option("part"), Some("2"));
+        assert_eq!(args.option("n"), Some("3"));
+        assert_eq!(args.option("verbose"), None);
+    }
+}
@@ -0,0 +1,42 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Named lines kept between commands, filled by `> name` and read by `< @name`
+#[derive(Default)]
+pub struct Buffers(BTreeMap<String, Vec<String>>);
+
+impl Buffers {
+    pub fn insert(&mut self, name: String, lines: Vec<String>) {
+        self.0.insert(name, lines);
+    }
+
+    /// Add a line at the end of a buffer, for the output of a job as it runs
+    pub fn push(&mut self, name: &str, line: String) {
+        self.0.entry(name.into()).or_default().push(line);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Vec<String>> {
+        self.0.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Vec<String>> {
+        self.0.remove(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let width = self.0.keys().map(String::len).max().unwrap_or_default();
+        self.0
+            .iter()
+            .map(|(name, lines)| {
+                let bytes: usize = lines.iter().map(String::len).sum();
+                format!("{:<width$}  {:>6} lines  {:>7} bytes", name, lines.len(), bytes)
+            })
+            .collect()
+    }
+}
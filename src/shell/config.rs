@@ -0,0 +1,326 @@
+use super::args::Aliases;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// How the console looks, changed at runtime with `set`
+#[derive(Clone, Debug)]
+pub struct ConsoleConfig {
+    /// before a command line
+    pub prompt: String,
+    /// before each input line of a command
+    pub input_prompt: String,
+    /// before each output line of a command
+    pub output_prompt: String,
+    /// before each error line, of a command or of the console
+    pub error_prompt: String,
+    /// before what is typed while a command runs
+    pub type_ahead_prompt: String,
+    /// send typed characters back, off when the terminal echoes them itself
+    pub echo: bool,
+    pub newline: Newline,
+    /// colours, `None` for plain text
+    pub theme: Option<Theme>,
+    /// most bytes typed or pasted as the input of a command, `None` to only keep the heap from running out
+    pub input_limit: Option<usize>,
+    /// most bytes of the command lines kept in the history, the oldest ones being dropped
+    pub history_limit: usize,
+    /// command names replaced by a command line, changed with `alias` and `unalias`
+    pub aliases: Aliases,
+    /// assumed until the terminal reports its own
+    pub size: TerminalSize,
+}
+
+impl Default for ConsoleConfig {
+    fn default() -> Self {
+        Self {
+            prompt: "$ ".into(),
+            input_prompt: "< ".into(),
+            output_prompt: "> ".into(),
+            error_prompt: "! ".into(),
+            type_ahead_prompt: "+ ".into(),
+            echo: true,
+            newline: Newline::CrLf,
+            theme: None,
+            input_limit: None,
+            history_limit: 2048,
+            aliases: Aliases::new(),
+            size: TerminalSize::default(),
+        }
+    }
+}
+
+/// Rows and columns of a terminal, given to commands so that their output fits
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct TerminalSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl Default for TerminalSize {
+    fn default() -> Self {
+        Self { rows: 24, cols: 80 }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Newline {
+    CrLf,
+    Lf,
+}
+
+/// SGR parameters of each kind of text, such as `"31"` for red
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Theme {
+    pub error: &'static str,
+    pub output: &'static str,
+    pub echo: &'static str,
+}
+
+impl Theme {
+    pub const DEFAULT: Theme = Theme {
+        error: "31",
+        output: "32",
+        echo: "1",
+    };
+}
+
+/// Start of a line written by the console
+#[derive(Clone, Copy)]
+pub(super) enum Marker {
+    Prompt,
+    Cancel,
+    Input,
+    Run,
+    Error,
+    TypeAhead,
+    /// first input line of a command, with bracketed paste enabled
+    InputStart,
+    /// end of input, disabling bracketed paste
+    InputEnd,
+    InputCancel,
+    InputPrompt,
+    /// input stopped by an error
+    InputError,
+}
+
+// bracketed paste is enabled while reading input lines, so that pasted control bytes are kept as text
+const PASTE_ON: &str = "\x1b[?2004h";
+const PASTE_OFF: &str = "\x1b[?2004l";
+/// Ask for the text area size, and for the position of the cursor moved as far as possible for terminals
+/// without `18t`, leaving the cursor where it was
+pub(super) const SIZE_QUERY: &[u8] = b"\x1b[18t\x1b7\x1b[999;999H\x1b[6n\x1b8";
+
+const SETTINGS: &[&str] = &[
+    "prompt",
+    "input-prompt",
+    "output-prompt",
+    "error-prompt",
+    "type-ahead-prompt",
+    "echo",
+    "newline",
+    "color",
+    "input-limit",
+    "history-limit",
+    "rows",
+    "cols",
+];
+
+impl ConsoleConfig {
+    pub(super) fn newline(&self) -> &'static str {
+        match self.newline {
+            Newline::CrLf => "\r\n",
+            Newline::Lf => "\n",
+        }
+    }
+
+    pub(super) fn marker(&self, marker: Marker) -> Vec<u8> {
+        let (before, prompt) = match marker {
+            Marker::Prompt => ("", &self.prompt),
+            Marker::Cancel => ("^C", &self.prompt),
+            Marker::Input => ("", &self.input_prompt),
+            Marker::Run => ("", &self.output_prompt),
+            Marker::Error => ("", &self.error_prompt),
+            Marker::TypeAhead => ("", &self.type_ahead_prompt),
+            Marker::InputStart => (PASTE_ON, &self.input_prompt),
+            Marker::InputEnd => (PASTE_OFF, &self.output_prompt),
+            Marker::InputCancel => ("\x1b[?2004l^C", &self.prompt),
+            Marker::InputPrompt => (PASTE_OFF, &self.prompt),
+            Marker::InputError => (PASTE_OFF, &self.error_prompt),
+        };
+        format!("{}{}{}", before, self.newline(), prompt).into_bytes()
+    }
+
+    /// Erase the current line and start it again with the prompt of `marker`
+    pub(super) fn replace_line(&self, marker: Marker) -> Vec<u8> {
+        let mut out = b"\r\x1b[K".to_vec();
+        out.extend_from_slice(self.prompt_of(marker).as_bytes());
+        out
+    }
+
+    pub(super) fn prompt_of(&self, marker: Marker) -> &str {
+        match marker {
+            Marker::Input | Marker::InputStart => &self.input_prompt,
+            Marker::Run | Marker::InputEnd => &self.output_prompt,
+            Marker::Error | Marker::InputError => &self.error_prompt,
+            Marker::TypeAhead => &self.type_ahead_prompt,
+            Marker::Prompt | Marker::Cancel | Marker::InputCancel | Marker::InputPrompt => &self.prompt,
+        }
+    }
+
+    /// Line of a script, without colours nor cursor moves so that the output is always the same
+    pub(super) fn framed(&self, prefix: &str, text: &str) -> Vec<u8> {
+        format!("{}{}{}", prefix, text, self.newline()).into_bytes()
+    }
+
+    pub(super) fn output(&self, line: &str) -> Vec<u8> {
+        self.colored(self.theme.map(|t| t.output), line.as_bytes())
+    }
+
+    pub(super) fn error(&self, err: &str) -> Vec<u8> {
+        self.colored(self.theme.map(|t| t.error), err.as_bytes())
+    }
+
+    /// What the terminal shows of the keys typed, nothing when it echoes them itself
+    pub(super) fn echo(&self, echo: Vec<u8>) -> Vec<u8> {
+        match (self.echo, self.theme) {
+            (false, _) => Vec::new(),
+            (true, Some(theme)) if !echo.is_empty() => self.colored(Some(theme.echo), &echo),
+            (true, _) => echo,
+        }
+    }
+
+    fn colored(&self, sgr: Option<&str>, text: &[u8]) -> Vec<u8> {
+        match sgr {
+            Some(sgr) => {
+                let mut out = format!("\x1b[{}m", sgr).into_bytes();
+                out.extend_from_slice(text);
+                out.extend_from_slice(b"\x1b[0m");
+                out
+            }
+            None => text.to_vec(),
+        }
+    }
+
+    pub(super) fn set(&mut self, name: &str, value: &str) -> Result<(), &'static str> {
+        match name {
+            "prompt" => self.prompt = value.into(),
+            "input-prompt" => self.input_prompt = value.into(),
+            "output-prompt" => self.output_prompt = value.into(),
+            "error-prompt" => self.error_prompt = value.into(),
+            "type-ahead-prompt" => self.type_ahead_prompt = value.into(),
+            "echo" => self.echo = on_off(value)?,
+            "newline" => {
+                self.newline = match value {
+                    "crlf" => Newline::CrLf,
+                    "lf" => Newline::Lf,
+                    _ => return Err("newline is crlf or lf"),
+                }
+            }
+            "color" => self.theme = on_off(value)?.then_some(Theme::DEFAULT),
+            "input-limit" => {
+                self.input_limit = match value {
+                    "off" => None,
+                    _ => Some(value.parse().map_err(|_| "input-limit is a number of bytes or off")?),
+                }
+            }
+            "history-limit" => self.history_limit = value.parse().map_err(|_| "history-limit is a number of bytes")?,
+            "rows" => self.size.rows = value.parse().ok().filter(|&rows| rows > 1).ok_or("rows is a number over 1")?,
+            "cols" => self.size.cols = value.parse().ok().filter(|&cols| cols > 0).ok_or("cols is a number over 0")?,
+            _ => return Err("unknown setting"),
+        }
+        Ok(())
+    }
+
+    /// Settings with their value, as shown by `set`
+    pub(super) fn lines(&self) -> Vec<String> {
+        let width = SETTINGS.iter().map(|s| s.len()).max().unwrap_or_default();
+        SETTINGS
+            .iter()
+            .map(|name| {
+                let value = match *name {
+                    "prompt" => format!("{:?}", self.prompt),
+                    "input-prompt" => format!("{:?}", self.input_prompt),
+                    "output-prompt" => format!("{:?}", self.output_prompt),
+                    "error-prompt" => format!("{:?}", self.error_prompt),
+                    "type-ahead-prompt" => format!("{:?}", self.type_ahead_prompt),
+                    "echo" => on_off_str(self.echo).into(),
+                    "newline" => if self.newline == Newline::CrLf { "crlf" } else { "lf" }.into(),
+                    "color" => on_off_str(self.theme.is_some()).into(),
+                    "rows" => self.size.rows.to_string(),
+                    "cols" => self.size.cols.to_string(),
+                    "history-limit" => self.history_limit.to_string(),
+                    _ => self.input_limit.map_or("off".into(), |limit| limit.to_string()),
+                };
+                format!("{:<width$}  {}", name, value)
+            })
+            .collect()
+    }
+
+    /// Candidates for the setting name, or for its value when it has a fixed set of values
+    pub(super) fn complete(args: &[&str], prefix: &str) -> Vec<String> {
+        let candidates: &[&str] = match args {
+            [] => SETTINGS,
+            ["echo" | "color"] => &["on", "off"],
+            ["newline"] => &["crlf", "lf"],
+            ["input-limit"] => &["off"],
+            _ => &[],
+        };
+        candidates
+            .iter()
+            .filter(|c| c.starts_with(prefix))
+            .map(ToString::to_string)
+            .collect()
+    }
+}
+
+fn on_off(value: &str) -> Result<bool, &'static str> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err("value is on or off"),
+    }
+}
+
+fn on_off_str(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let mut config = ConsoleConfig::default();
+        assert_eq!(config.marker(Marker::Prompt), b"\r\n$ ");
+        assert_eq!(config.marker(Marker::InputStart), b"\x1b[?2004h\r\n< ");
+        assert_eq!(config.replace_line(Marker::Run), b"\r\x1b[K> ");
+        assert_eq!(config.echo(b"abc".to_vec()), b"abc");
+        config.set("prompt", "% ").unwrap();
+        config.set("newline", "lf").unwrap();
+        config.set("color", "on").unwrap();
+        assert_eq!(config.marker(Marker::Cancel), b"^C\n% ");
+        assert_eq!(config.error("bad"), b"\x1b[31mbad\x1b[0m");
+        assert_eq!(config.echo(Vec::new()), b"");
+        config.set("echo", "off").unwrap();
+        assert_eq!(config.echo(b"abc".to_vec()), b"");
+        assert_eq!(config.set("echo", "yes"), Err("value is on or off"));
+        assert_eq!(config.set("colour", "on"), Err("unknown setting"));
+        assert_eq!(config.lines()[0], "prompt             \"% \"");
+        assert_eq!(ConsoleConfig::complete(&["color"], "o"), ["on", "off"]);
+        config.set("input-limit", "4096").unwrap();
+        assert_eq!(config.input_limit, Some(4096));
+        assert_eq!(config.lines()[8], "input-limit        4096");
+        assert_eq!(config.set("input-limit", "4k"), Err("input-limit is a number of bytes or off"));
+        config.set("input-limit", "off").unwrap();
+        assert_eq!(config.input_limit, None);
+        config.set("history-limit", "512").unwrap();
+        assert_eq!(config.lines()[9], "history-limit      512");
+        assert_eq!(config.set("history-limit", "off"), Err("history-limit is a number of bytes"));
+        config.set("cols", "132").unwrap();
+        assert_eq!(config.size, TerminalSize { rows: 24, cols: 132 });
+        assert_eq!(config.lines()[11], "cols               132");
+        assert_eq!(config.set("rows", "1"), Err("rows is a number over 1"));
+    }
+}
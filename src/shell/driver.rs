@@ -0,0 +1,185 @@
+//! Running a console without an executor, for tests and tools on Linux
+//!
+//! A transcript is a text file of input and expected output bytes, one quoted string per line:
+//!
+//! ```text
+//! # comment
+//! < "cat <\r"
+//! > "cat <\x1b[?2004h\r\n< "
+//! ```
+//!
+//! Each `<` line is given to the console, which then runs until it waits for more input; the `>` lines
+//! following it are all its output. Strings take the `\r`, `\n`, `\t`, `\e`, `\0`, `\\`, `\"` and `\xNN`
+//! escapes.
+
+extern crate std;
+
+use super::{AsyncInputIterator, Console};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Waker};
+
+/// Owns a console and polls it on each `next`, its input being pushed to the queue of its parser
+pub struct ConsoleDriver<'a> {
+    // loops over `next_wait`, so that a console waiting for input keeps its state
+    future: Pin<Box<dyn Future<Output = ()> + 'a>>,
+    outputs: Rc<RefCell<VecDeque<Vec<u8>>>>,
+}
+
+impl<'a> ConsoleDriver<'a> {
+    pub fn new<I: AsyncInputIterator + 'a>(mut console: Console<I>) -> Self {
+        let outputs = Rc::new(RefCell::new(VecDeque::new()));
+        let sink = outputs.clone();
+        let future = Box::pin(async move {
+            loop {
+                let (out1, out2) = console.next_wait().await;
+                let out = [out1, out2].concat();
+                if !out.is_empty() {
+                    sink.borrow_mut().push_back(out);
+                }
+            }
+        });
+        Self { future, outputs }
+    }
+
+    /// All the output of the console until it waits for input
+    pub fn run(&mut self) -> Vec<u8> {
+        self.by_ref().flatten().collect()
+    }
+
+    /// Play the steps of a transcript, `push` giving input bytes to the console
+    pub fn check(&mut self, transcript: &Transcript, push: impl Fn(Vec<u8>)) -> Result<(), String> {
+        for (line, input, expected) in &transcript.steps {
+            push(input.clone());
+            let out = self.run();
+            if &out != expected {
+                return Err(format!(
+                    "line {}: expected {:?}, got {:?}",
+                    line,
+                    String::from_utf8_lossy(expected),
+                    String::from_utf8_lossy(&out)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outputs of the console, `None` once it waits for input
+impl Iterator for ConsoleDriver<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if self.outputs.borrow().is_empty() {
+            let _ = self.future.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+        }
+        self.outputs.borrow_mut().pop_front()
+    }
+}
+
+/// Input bytes and the expected output, with the line of the input in the file
+pub struct Transcript {
+    steps: Vec<(usize, Vec<u8>, Vec<u8>)>,
+}
+
+impl Transcript {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut steps: Vec<(usize, Vec<u8>, Vec<u8>)> = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |err| format!("line {}: {}", idx + 1, err);
+            let (kind, quoted) = line.split_at_checked(1).ok_or_else(|| error("lines start with <, > or #"))?;
+            let bytes = unquote(quoted.trim()).map_err(error)?;
+            match (kind, steps.last_mut()) {
+                ("<", _) => steps.push((idx + 1, bytes, Vec::new())),
+                (">", Some((_, _, out))) => out.extend(bytes),
+                (">", None) => return Err(error("output before any input")),
+                _ => return Err(error("lines start with <, > or #")),
+            }
+        }
+        Ok(Self { steps })
+    }
+}
+
+fn unquote(quoted: &str) -> Result<Vec<u8>, &'static str> {
+    let inner = quoted
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or("expected a quoted string")?;
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next() {
+            Some('r') => b'\r',
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('e') => b'\x1b',
+            Some('0') => b'\0',
+            Some(c @ ('\\' | '"')) => c as u8,
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16).map_err(|_| "bad \\x escape")?
+            }
+            _ => return Err("bad escape"),
+        });
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::{Commands, ConsoleConfig, InputParser, MutexQueue};
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn test_transcript() {
+        let transcript = Transcript::parse("# c\n< \"a\\r\"\n> \"\\e[1m \"\n>\"\\x41\\\"\"\n").unwrap();
+        assert_eq!(transcript.steps, [(2, b"a\r".to_vec(), b"\x1b[1m A\"".to_vec())]);
+        assert_eq!(Transcript::parse("> \"a\"").err().as_deref(), Some("line 1: output before any input"));
+        assert_eq!(Transcript::parse("< a").err().as_deref(), Some("line 1: expected a quoted string"));
+        assert_eq!(Transcript::parse("é").err().as_deref(), Some("line 1: lines start with <, > or #"));
+    }
+
+    static NOW: AtomicU64 = AtomicU64::new(0);
+
+    #[test]
+    fn test_transcripts() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shell/transcripts");
+        let mut paths: Vec<_> = std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        for path in paths {
+            let transcript = Transcript::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let queue = MutexQueue::new();
+            let console = Console::new(InputParser::new(queue.clone()), Commands::default(), ConsoleConfig::default())
+                .with_clock(|| NOW.load(Ordering::Relaxed))
+                // the next input wakes the console instead, its pager checking the deadline again
+                .with_timer(|_, _| {});
+            let mut driver = ConsoleDriver::new(console);
+            // each input comes 150 ms after the previous one
+            let push = |input| {
+                NOW.fetch_add(150, Ordering::Relaxed);
+                queue.push(input);
+            };
+            if let Err(err) = driver.check(&transcript, push) {
+                panic!("{}: {}", path.display(), err);
+            }
+        }
+    }
+}
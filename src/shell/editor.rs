@@ -0,0 +1,436 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use super::unicode::{self, width};
+use super::{Input, EscapeSequence, Key, COLS};
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Edit {
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    KillStart,
+    KillWord,
+    KillEnd,
+}
+
+impl Edit {
+    pub fn from_input(input: &Input) -> Option<Self> {
+        match input {
+            Input::Control('\x7f' | '\x08') => Some(Edit::Backspace),
+            Input::Control('\x15') => Some(Edit::KillStart),
+            Input::Control('\x17') => Some(Edit::KillWord),
+            Input::Control('\x0b') => Some(Edit::KillEnd),
+            Input::EscapeSequence(EscapeSequence::Key(key, _)) => match key {
+                Key::Left => Some(Edit::Left),
+                Key::Right => Some(Edit::Right),
+                Key::Home => Some(Edit::Home),
+                Key::End => Some(Edit::End),
+                Key::Delete => Some(Edit::Delete),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Current line being typed, with a cursor.
+///
+/// Every change returns the bytes to send to the terminal so that it displays the same line. The cursor
+/// moves over whole grapheme clusters and columns are counted in display width, so that wide characters
+/// and combining marks are shown where the terminal puts them.
+///
+/// A line longer than the terminal is wide takes several rows: positions are counted in columns from the
+/// start of the row of the prompt, and the cursor goes up and down to reach them.
+///
+/// Control characters, which only a paste puts in the line, are shown as `^X` or `\xNN` rather than acted on
+/// by the terminal.
+pub struct LineEditor {
+    line: String,
+    // byte offset in `line`, always on a char boundary
+    cursor: usize,
+    // column of the first character, after the prompt
+    start: usize,
+    // width of the terminal
+    cols: usize,
+}
+
+impl Default for LineEditor {
+    /// Line never wrapping, after an empty prompt
+    fn default() -> Self {
+        Self {
+            line: String::with_capacity(COLS),
+            cursor: 0,
+            start: 0,
+            cols: usize::MAX,
+        }
+    }
+}
+
+impl LineEditor {
+    /// Empty line after a prompt `start` columns wide, on a terminal `cols` columns wide
+    pub fn new(start: usize, cols: usize) -> Self {
+        Self {
+            start,
+            cols: cols.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// Wrap at a new terminal width, the line being shown again afterward
+    pub fn resize(&mut self, cols: usize) {
+        self.cols = cols.max(1);
+    }
+
+    /// Move the line after another prompt, the line being shown again afterward
+    pub fn set_prompt_width(&mut self, start: usize) {
+        self.start = start;
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.line
+    }
+
+    pub fn before_cursor(&self) -> &str {
+        &self.line[..self.cursor]
+    }
+
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        core::mem::replace(&mut self.line, String::with_capacity(COLS))
+    }
+
+    /// Write the whole line on a fresh prompt and put the cursor back in place
+    pub fn redisplay(&self) -> Vec<u8> {
+        let mut out = self.drawn(0);
+        let end = self.position(self.line.len());
+        out.extend_from_slice(self.wrap(self.start, end));
+        out.extend(self.move_to(end, self.position(self.cursor)));
+        out
+    }
+
+    /// Replace the whole line, leaving the cursor at the end
+    pub fn replace(&mut self, s: &str) -> Vec<u8> {
+        let (displayed, old_end) = (self.position(self.cursor), self.position(self.line.len()));
+        self.line.clear();
+        self.line.push_str(s);
+        self.cursor = self.line.len();
+        self.redraw(displayed, 0, old_end)
+    }
+
+    pub fn insert(&mut self, s: &str) -> Vec<u8> {
+        if s.is_empty() {
+            return Vec::new();
+        }
+        let (displayed, old_end) = (self.position(self.cursor), self.position(self.line.len()));
+        self.line.insert_str(self.cursor, s);
+        let from = self.cursor;
+        self.cursor += s.len();
+        if self.cursor == self.line.len() {
+            let mut out = self.drawn(from);
+            out.extend_from_slice(self.wrap(displayed, self.position(self.cursor)));
+            return out;
+        }
+        self.redraw(displayed, from, old_end)
+    }
+
+    /// Move the cursor up to the row of the prompt, erasing the rows below when the line takes several,
+    /// before the prompt row is written again
+    pub fn to_first_row(&self) -> Vec<u8> {
+        let mut out = move_rows(self.position(self.cursor) / self.cols, 0);
+        if self.position(self.line.len()) >= self.cols {
+            out.extend_from_slice(b"\r\x1b[J");
+        }
+        out
+    }
+
+    /// Move the cursor down to the last row of the line, so that a new line starts below it
+    pub fn to_last_row(&self) -> Vec<u8> {
+        move_rows(self.position(self.cursor) / self.cols, self.position(self.line.len()) / self.cols)
+    }
+
+    pub fn edit(&mut self, edit: Edit) -> Vec<u8> {
+        let old_pos = self.position(self.cursor);
+        let old_end = self.position(self.line.len());
+        let old_len = self.line.len();
+        match edit {
+            Edit::Left => {
+                self.cursor = self.prev_boundary(self.cursor);
+                return self.move_to(old_pos, self.position(self.cursor));
+            }
+            Edit::Right => {
+                self.cursor = self.next_boundary(self.cursor);
+                return self.move_to(old_pos, self.position(self.cursor));
+            }
+            Edit::Home => {
+                self.cursor = 0;
+                return self.move_to(old_pos, self.position(self.cursor));
+            }
+            Edit::End => {
+                self.cursor = self.line.len();
+                return self.move_to(old_pos, self.position(self.cursor));
+            }
+            Edit::Backspace => {
+                let start = self.prev_boundary(self.cursor);
+                self.line.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            Edit::Delete => {
+                let end = self.next_boundary(self.cursor);
+                self.line.replace_range(self.cursor..end, "");
+            }
+            Edit::KillStart => {
+                self.line.replace_range(..self.cursor, "");
+                self.cursor = 0;
+            }
+            Edit::KillWord => {
+                let before = self.line[..self.cursor].trim_end_matches(' ');
+                let start = before.rfind(' ').map(|i| i + 1).unwrap_or(0);
+                self.line.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            Edit::KillEnd => {
+                self.line.truncate(self.cursor);
+            }
+        }
+        if old_len == self.line.len() {
+            return Vec::new();
+        }
+        self.redraw(old_pos, self.cursor, old_end)
+    }
+
+    /// Columns from the start of the prompt row to the character at byte offset `offset`
+    fn position(&self, offset: usize) -> usize {
+        self.place(offset).1
+    }
+
+    /// Columns from the start of the prompt row to the end of the text before byte offset `offset`, and to
+    /// the character there
+    fn place(&self, offset: usize) -> (usize, usize) {
+        let mut layout = self.layout().skip_while(|&(at, ..)| at < offset);
+        layout.next().map_or((self.start, self.start), |(_, before, placed)| (before, placed))
+    }
+
+    /// Byte offset of each grapheme cluster and of the end of the line, with the columns to the end of the
+    /// text before it and to where it is placed: as on the terminal, a wide character does not start in the
+    /// last column of a row
+    fn layout(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        let (mut at, mut pos, mut done) = (0, self.start, false);
+        core::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let next = self.next_boundary(at);
+            done = next == at;
+            let grapheme = &self.line[at..next];
+            let wide = grapheme.chars().next().is_some_and(|c| unicode::char_width(c) == 2);
+            let placed = if wide && self.cols > 1 && pos % self.cols == self.cols - 1 { pos + 1 } else { pos };
+            let item = (at, pos, placed);
+            (at, pos) = (next, placed + width(&shown(grapheme)));
+            Some(item)
+        })
+    }
+
+    /// Text written for the line from byte offset `from`, with a space in the last column of the rows
+    /// ending before a wide character so that nothing is left there
+    fn drawn(&self, from: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (at, before, placed) in self.layout().skip_while(|&(at, ..)| at < from) {
+            if placed > before {
+                out.push(b' ');
+            }
+            out.extend_from_slice(shown(&self.line[at..self.next_boundary(at)]).as_bytes());
+        }
+        out
+    }
+
+    /// Redraw the line from byte offset `from`, the terminal cursor being at position `displayed` and the
+    /// line shown before ending at position `old_end`
+    fn redraw(&self, displayed: usize, from: usize, old_end: usize) -> Vec<u8> {
+        let mut out = self.move_to(displayed, self.place(from).0);
+        out.extend(self.drawn(from));
+        let end = self.position(self.line.len());
+        out.extend_from_slice(self.wrap(self.position(from), end));
+        // the rows the line does not take anymore are erased too
+        let erase: &[u8] = if old_end / self.cols > end / self.cols { b"\x1b[J" } else { b"\x1b[K" };
+        out.extend_from_slice(erase);
+        out.extend(self.move_to(end, self.position(self.cursor)));
+        out
+    }
+
+    /// After writing from position `from` up to the last column, the terminal waits for another character
+    /// before wrapping: going to the next row puts the cursor where its position says
+    fn wrap(&self, from: usize, end: usize) -> &'static [u8] {
+        if end > from && end.is_multiple_of(self.cols) { b"\r\n" } else { b"" }
+    }
+
+    fn move_to(&self, from: usize, to: usize) -> Vec<u8> {
+        let (from_row, to_row) = (from / self.cols, to / self.cols);
+        if from_row == to_row {
+            return if to < from { move_left(from - to) } else { move_right(to - from) };
+        }
+        let mut out = move_rows(from_row, to_row);
+        out.push(b'\r');
+        out.extend(move_right(to % self.cols));
+        out
+    }
+
+    fn prev_boundary(&self, pos: usize) -> usize {
+        unicode::prev_boundary(&self.line, pos)
+    }
+
+    fn next_boundary(&self, pos: usize) -> usize {
+        unicode::next_boundary(&self.line, pos)
+    }
+}
+
+/// Text written for `s`, with control characters in caret notation, or as `\xNN` for those above `\x7f`
+fn shown(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(char::is_control) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len() + 8);
+    for c in s.chars() {
+        match c {
+            '\0'..='\x1f' => {
+                out.push('^');
+                out.push(char::from(c as u8 + 0x40));
+            }
+            '\x7f' => out.push_str("^?"),
+            c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+fn move_left(n: usize) -> Vec<u8> {
+    match n {
+        0 => Vec::new(),
+        1 => b"\x08".into(),
+        n => format!("\x1b[{}D", n).into_bytes(),
+    }
+}
+
+fn move_right(n: usize) -> Vec<u8> {
+    match n {
+        0 => Vec::new(),
+        n => format!("\x1b[{}C", n).into_bytes(),
+    }
+}
+
+fn move_rows(from: usize, to: usize) -> Vec<u8> {
+    match to.cmp(&from) {
+        Ordering::Less => format!("\x1b[{}A", from - to).into_bytes(),
+        Ordering::Equal => Vec::new(),
+        Ordering::Greater => format!("\x1b[{}B", to - from).into_bytes(),
+    }
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_editor() {
+        let mut editor = LineEditor::default();
+        assert_eq!(editor.insert("abc"), b"abc");
+        assert_eq!(editor.edit(Edit::Left), b"\x08");
+        assert_eq!(editor.insert("X"), b"Xc\x1b[K\x08");
+        assert_eq!(editor.edit(Edit::Backspace), b"\x08c\x1b[K\x08");
+        assert_eq!(editor.edit(Edit::Home), b"\x1b[2D");
+        assert_eq!(editor.edit(Edit::Delete), b"bc\x1b[K\x1b[2D");
+        assert_eq!(editor.edit(Edit::Backspace), b"");
+        assert_eq!(editor.edit(Edit::End), b"\x1b[2C");
+        assert_eq!(editor.take(), "bc");
+    }
+
+    #[test]
+    fn test_line_editor_kill() {
+        let mut editor = LineEditor::default();
+        editor.insert("aoc 16 <");
+        assert_eq!(editor.edit(Edit::KillWord), b"\x08\x1b[K");
+        assert_eq!(editor.edit(Edit::KillWord), b"\x1b[3D\x1b[K");
+        editor.edit(Edit::Home);
+        editor.edit(Edit::Right);
+        assert_eq!(editor.edit(Edit::KillEnd), b"\x1b[K");
+        assert_eq!(editor.edit(Edit::KillStart), b"\x08\x1b[K");
+        assert!(editor.as_str().is_empty());
+        editor.insert("aoc 16");
+        editor.edit(Edit::Left);
+        assert_eq!(editor.edit(Edit::KillWord), b"\x086\x1b[K\x08");
+        assert_eq!(editor.take(), "aoc 6");
+    }
+
+    #[test]
+    fn test_line_editor_replace() {
+        let mut editor = LineEditor::default();
+        editor.insert("aoc 1");
+        editor.edit(Edit::Left);
+        assert_eq!(editor.replace("aoc 16 <"), b"\x1b[4Daoc 16 <\x1b[K");
+        assert_eq!(editor.as_str(), "aoc 16 <");
+    }
+
+    #[test]
+    fn test_line_editor_control() {
+        let mut editor = LineEditor::default();
+        assert_eq!(editor.insert("a\x1b[Ab\x04\u{85}"), b"a^[[Ab^D\\x85");
+        assert_eq!(editor.edit(Edit::Left), b"\x1b[4D");
+        assert_eq!(editor.edit(Edit::Left), b"\x1b[2D");
+        assert_eq!(editor.edit(Edit::Backspace), b"\x08^D\\x85\x1b[K\x1b[6D");
+        assert_eq!(editor.take(), "a\x1b[A\x04\u{85}");
+    }
+
+    #[test]
+    fn test_line_editor_unicode() {
+        let mut editor = LineEditor::default();
+        assert_eq!(editor.insert("日本e\u{301}"), "日本e\u{301}".as_bytes());
+        assert_eq!(editor.edit(Edit::Left), b"\x08");
+        assert_eq!(editor.edit(Edit::Left), b"\x1b[2D");
+        assert_eq!(editor.edit(Edit::Backspace), "\x1b[2D本e\u{301}\x1b[K\x1b[3D".as_bytes());
+        assert_eq!(editor.edit(Edit::End), b"\x1b[3C");
+        assert_eq!(editor.edit(Edit::Backspace), b"\x08\x1b[K");
+        assert_eq!(editor.take(), "本");
+    }
+
+    #[test]
+    fn test_line_editor_wrap() {
+        let mut editor = LineEditor::new(2, 10);
+        assert_eq!(editor.insert("abcdefgh"), b"abcdefgh\r\n");
+        assert_eq!(editor.insert("ij"), b"ij");
+        assert_eq!(editor.edit(Edit::Home), b"\x1b[1A\r\x1b[2C");
+        assert_eq!(editor.to_first_row(), b"\r\x1b[J");
+        assert_eq!(editor.to_last_row(), b"\x1b[1B");
+        assert_eq!(editor.edit(Edit::End), b"\x1b[1B\r\x1b[2C");
+        assert_eq!(editor.to_first_row(), b"\x1b[1A\r\x1b[J");
+        assert_eq!(editor.edit(Edit::Backspace), b"\x08\x1b[K");
+        assert_eq!(editor.edit(Edit::KillStart), b"\x1b[1A\r\x1b[2C\x1b[J");
+        assert_eq!(editor.to_first_row(), b"");
+        editor.resize(4);
+        assert_eq!(editor.insert("abcdef"), b"abcdef\r\n");
+        assert_eq!(editor.redisplay(), b"abcdef\r\n");
+    }
+
+    #[test]
+    fn test_line_editor_wide_wrap() {
+        // a wide character at the last column starts the next row, that column staying empty
+        let mut editor = LineEditor::new(2, 10);
+        assert_eq!(editor.insert("abcdefg日"), "abcdefg 日".as_bytes());
+        assert_eq!(editor.edit(Edit::Left), b"\x1b[2D");
+        assert_eq!(editor.edit(Edit::Left), b"\x1b[1A\r\x1b[8C");
+        assert_eq!(editor.to_last_row(), b"\x1b[1B");
+        assert_eq!(editor.insert("h"), "hg日\x1b[K\x1b[1A\r\x1b[9C".as_bytes());
+        assert_eq!(editor.edit(Edit::Backspace), "\x08g 日\x1b[K\x1b[1A\r\x1b[8C".as_bytes());
+        assert_eq!(editor.edit(Edit::End), b"\x1b[1B\r\x1b[2C");
+        assert_eq!(editor.redisplay(), "abcdefg 日".as_bytes());
+        editor.resize(12);
+        assert_eq!(editor.insert("日"), " 日".as_bytes());
+        assert_eq!(editor.edit(Edit::Left), b"\x1b[2D");
+        assert_eq!(editor.edit(Edit::Left), b"\x1b[1A\r\x1b[9C");
+    }
+}
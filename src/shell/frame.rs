@@ -0,0 +1,203 @@
+//! Protocol for host programs, entered by sending `HANDSHAKE` at the prompt
+//!
+//! Each message is COBS-encoded and ended by a zero byte. Its first byte is its kind, followed by
+//! fields, each one being a little-endian `u32` length and that many bytes.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Escape sequence switching the console to framed messages, answered by `Response::Ready`
+pub const HANDSHAKE: &[u8] = b"\x1b[=F";
+
+const VERSION: &str = "aoc-pico 1";
+
+/// Message from the host
+#[derive(Eq, PartialEq, Debug)]
+pub enum Request {
+    /// Run a command, its input lines being the lines of `input`
+    Run { name: String, args: Vec<String>, input: Vec<u8> },
+    /// Stop the running command
+    Cancel,
+    /// Back to the interactive console
+    Quit,
+}
+
+/// Message to the host
+#[derive(Eq, PartialEq, Debug)]
+pub enum Response<'a> {
+    /// Framed mode entered, with the protocol version
+    Ready(&'a str),
+    Line(&'a str),
+    Error(&'a str),
+    /// Last message for a request
+    Status(u8),
+}
+
+impl Request {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut message = Vec::new();
+        match self {
+            Request::Run { name, args, input } => {
+                message.push(b'r');
+                push_field(&mut message, name.as_bytes());
+                for arg in args {
+                    push_field(&mut message, arg.as_bytes());
+                }
+                push_field(&mut message, input);
+            }
+            Request::Cancel => message.push(b'c'),
+            Request::Quit => message.push(b'q'),
+        }
+        frame(&message)
+    }
+
+    /// Request of a message once COBS-decoded
+    pub fn decode(message: &[u8]) -> Option<Self> {
+        let (&kind, mut fields) = message.split_first()?;
+        let mut decoded = Vec::new();
+        while !fields.is_empty() {
+            let (field, rest) = split_field(fields)?;
+            decoded.push(field);
+            fields = rest;
+        }
+        match (kind, decoded.as_slice()) {
+            (b'r', [name, args @ .., input]) => Some(Request::Run {
+                name: String::from_utf8(name.to_vec()).ok()?,
+                args: args
+                    .iter()
+                    .map(|arg| String::from_utf8(arg.to_vec()).ok())
+                    .collect::<Option<_>>()?,
+                input: input.to_vec(),
+            }),
+            (b'c', []) => Some(Request::Cancel),
+            (b'q', []) => Some(Request::Quit),
+            _ => None,
+        }
+    }
+}
+
+impl Response<'_> {
+    pub const READY: Response<'static> = Response::Ready(VERSION);
+
+    pub fn encode(&self) -> Vec<u8> {
+        let (kind, field) = match self {
+            Response::Ready(version) => (b'v', version.as_bytes()),
+            Response::Line(line) => (b'o', line.as_bytes()),
+            Response::Error(err) => (b'e', err.as_bytes()),
+            Response::Status(status) => (b's', core::slice::from_ref(status)),
+        };
+        let mut message = Vec::from([kind]);
+        push_field(&mut message, field);
+        frame(&message)
+    }
+
+    /// Response of a message once COBS-decoded
+    pub fn decode(message: &[u8]) -> Option<Response<'_>> {
+        let (&kind, fields) = message.split_first()?;
+        let (field, rest) = split_field(fields)?;
+        if !rest.is_empty() {
+            return None;
+        }
+        let text = || core::str::from_utf8(field).ok();
+        match (kind, field) {
+            (b'v', _) => Some(Response::Ready(text()?)),
+            (b'o', _) => Some(Response::Line(text()?)),
+            (b'e', _) => Some(Response::Error(text()?)),
+            (b's', &[status]) => Some(Response::Status(status)),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a decoded message ends the framed mode
+pub(super) fn is_quit(message: &[u8]) -> bool {
+    message == b"q"
+}
+
+fn push_field(message: &mut Vec<u8>, field: &[u8]) {
+    message.extend_from_slice(&(field.len() as u32).to_le_bytes());
+    message.extend_from_slice(field);
+}
+
+fn split_field(fields: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, rest) = fields.split_first_chunk::<4>()?;
+    let len = u32::from_le_bytes(*len) as usize;
+    (rest.len() >= len).then(|| rest.split_at(len))
+}
+
+/// COBS-encoded message followed by its zero delimiter
+fn frame(message: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(message.len() + message.len() / 254 + 2);
+    let mut code_idx = 0;
+    encoded.push(0);
+    for &b in message {
+        if b != 0 {
+            encoded.push(b);
+        }
+        if b == 0 || encoded.len() - code_idx == 0xff {
+            encoded[code_idx] = (encoded.len() - code_idx) as u8;
+            code_idx = encoded.len();
+            encoded.push(0);
+        }
+    }
+    encoded[code_idx] = (encoded.len() - code_idx) as u8;
+    encoded.push(0);
+    encoded
+}
+
+/// Message of a COBS-encoded frame, without its zero delimiter
+pub fn unframe(encoded: &[u8]) -> Option<Vec<u8>> {
+    let mut message = Vec::with_capacity(encoded.len());
+    let mut rest = encoded;
+    while let Some((&code, after)) = rest.split_first() {
+        let len = usize::from(code).checked_sub(1)?;
+        if after.len() < len {
+            return None;
+        }
+        let (block, after) = after.split_at(len);
+        message.extend_from_slice(block);
+        rest = after;
+        if code != 0xff && !rest.is_empty() {
+            message.push(0);
+        }
+    }
+    Some(message)
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_cobs() {
+        assert_eq!(frame(b""), b"\x01\x00");
+        assert_eq!(frame(b"\x00"), b"\x01\x01\x00");
+        assert_eq!(frame(b"a\x00bc"), b"\x02a\x03bc\x00");
+        let long: Vec<u8> = (1..=255).collect();
+        let framed = frame(&long);
+        assert_eq!(framed.len(), 255 + 2 + 1);
+        assert_eq!(unframe(&framed[..framed.len() - 1]), Some(long));
+        assert_eq!(unframe(b"\x02a\x03bc"), Some(b"a\x00bc".to_vec()));
+        assert_eq!(unframe(b"\x05a"), None);
+    }
+
+    #[test]
+    fn test_messages() {
+        let run = Request::Run {
+            name: "aoc".into(),
+            args: vec!["1".into()],
+            input: b"3   4\n4   3\n".to_vec(),
+        };
+        let framed = run.encode();
+        assert_eq!(framed.last(), Some(&0));
+        assert!(!framed[..framed.len() - 1].contains(&0));
+        assert_eq!(Request::decode(&unframe(&framed[..framed.len() - 1]).unwrap()), Some(run));
+        assert_eq!(Request::decode(b"q"), Some(Request::Quit));
+        assert_eq!(Request::decode(b"r"), None);
+        assert_eq!(Request::decode(b"r\x05\x00\x00\x00ab"), None);
+        let framed = Response::Status(2).encode();
+        assert_eq!(Response::decode(&unframe(&framed[..framed.len() - 1]).unwrap()), Some(Response::Status(2)));
+        assert_eq!(Response::decode(b"o\x02\x00\x00\x00ok"), Some(Response::Line("ok")));
+    }
+}
@@ -0,0 +1,193 @@
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+const HISTORY_ENTRIES: usize = 32;
+
+/// Last command lines, bounded both in number of entries and in total size
+pub struct History {
+    entries: VecDeque<String>,
+    // number of `entries[0]`, as used by `!n`
+    first: usize,
+    bytes: usize,
+    // most bytes kept, the `history-limit` setting
+    limit: usize,
+    // index in `entries` while browsing with up/down, and the line being typed before that
+    browsing: Option<(usize, String)>,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(HISTORY_ENTRIES),
+            first: 1,
+            bytes: 0,
+            limit,
+            browsing: None,
+        }
+    }
+
+    pub fn push(&mut self, line: &str) {
+        self.browsing = None;
+        let line = line.trim();
+        if line.is_empty() || line.len() > self.limit || self.entries.back().is_some_and(|l| l == line) {
+            return;
+        }
+        while self.entries.len() >= HISTORY_ENTRIES || self.bytes + line.len() > self.limit {
+            self.drop_oldest();
+        }
+        self.bytes += line.len();
+        self.entries.push_back(line.to_string());
+    }
+
+    /// Keep at most `limit` bytes, dropping the oldest entries over it
+    pub fn set_limit(&mut self, limit: usize) {
+        self.browsing = None;
+        self.limit = limit;
+        while self.bytes > limit {
+            self.drop_oldest();
+        }
+    }
+
+    fn drop_oldest(&mut self) {
+        if let Some(old) = self.entries.pop_front() {
+            self.bytes -= old.len();
+            self.first += 1;
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, line)| format!("{:>4}  {}", self.first + i, line))
+            .collect()
+    }
+
+    /// Older entry than the one displayed, saving `current` when starting to browse
+    pub fn previous(&mut self, current: &str) -> Option<&str> {
+        let idx = match &self.browsing {
+            None => self.entries.len().checked_sub(1)?,
+            Some((0, _)) => return None,
+            Some((idx, _)) => idx - 1,
+        };
+        let saved = self.browsing.take().map(|(_, s)| s).unwrap_or_else(|| current.to_string());
+        self.browsing = Some((idx, saved));
+        Some(&self.entries[idx])
+    }
+
+    /// Newer entry than the one displayed, or the saved line after the last one
+    pub fn next(&mut self) -> Option<String> {
+        let (idx, saved) = self.browsing.take()?;
+        if idx + 1 < self.entries.len() {
+            let line = self.entries[idx + 1].clone();
+            self.browsing = Some((idx + 1, saved));
+            Some(line)
+        } else {
+            Some(saved)
+        }
+    }
+
+    /// Expand `!!`, `!n` and `!prefix`, returns `None` when there is nothing to expand
+    pub fn expand(&self, line: &str) -> Result<Option<String>, &'static str> {
+        if !line.contains('!') {
+            return Ok(None);
+        }
+        let mut out = String::with_capacity(line.len());
+        let mut expanded = false;
+        // `!` is kept as is between single quotes
+        let mut quote = None;
+        let mut rest = line;
+        while let Some(pos) = rest.find(['!', '\'', '"']) {
+            out.push_str(&rest[..pos]);
+            let c = char::from(rest.as_bytes()[pos]);
+            rest = &rest[pos + 1..];
+            if c != '!' || quote == Some('\'') {
+                quote = match quote {
+                    None if c != '!' => Some(c),
+                    Some(q) if q == c => None,
+                    q => q,
+                };
+                out.push(c);
+                continue;
+            }
+            let end = rest.find(' ').unwrap_or(rest.len());
+            let (event, after) = if let Some(after) = rest.strip_prefix('!') {
+                (self.entries.back(), after)
+            } else if end == 0 {
+                out.push('!');
+                continue;
+            } else if let Ok(n) = rest[..end].parse::<usize>() {
+                let entry = n.checked_sub(self.first).and_then(|i| self.entries.get(i));
+                (entry, &rest[end..])
+            } else {
+                let prefix = &rest[..end];
+                (self.entries.iter().rev().find(|l| l.starts_with(prefix)), &rest[end..])
+            };
+            out.push_str(event.ok_or("event not found")?);
+            expanded = true;
+            rest = after;
+        }
+        out.push_str(rest);
+        Ok(expanded.then_some(out))
+    }
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+
+    const LIMIT: usize = 2048;
+
+    #[test]
+    fn test_history_expand() {
+        let mut history = History::new(LIMIT);
+        history.push("aoc 16 <");
+        history.push("aoc 1 <");
+        history.push("history");
+        assert_eq!(history.expand("aoc 2"), Ok(None));
+        assert_eq!(history.expand("!!"), Ok(Some("history".into())));
+        assert_eq!(history.expand("!1"), Ok(Some("aoc 16 <".into())));
+        assert_eq!(history.expand("!aoc"), Ok(Some("aoc 1 <".into())));
+        assert_eq!(history.expand("echo ! !2"), Ok(Some("echo ! aoc 1 <".into())));
+        assert_eq!(history.expand("!12"), Err("event not found"));
+        assert_eq!(history.expand("!foo"), Err("event not found"));
+        assert_eq!(history.expand("echo 'hi!' !!"), Ok(Some("echo 'hi!' history".into())));
+        assert_eq!(history.expand("echo 'hi!'"), Ok(None));
+        assert_eq!(history.expand("echo \"it's\" !!"), Ok(Some("echo \"it's\" history".into())));
+    }
+
+    #[test]
+    fn test_history_browse() {
+        let mut history = History::new(LIMIT);
+        history.push("aoc 1 <");
+        history.push("aoc 2 <");
+        assert_eq!(history.previous("typed"), Some("aoc 2 <"));
+        assert_eq!(history.previous("ignored"), Some("aoc 1 <"));
+        assert_eq!(history.previous("ignored"), None);
+        assert_eq!(history.next(), Some("aoc 2 <".into()));
+        assert_eq!(history.next(), Some("typed".into()));
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn test_history_bounds() {
+        let mut history = History::new(LIMIT);
+        for i in 0..HISTORY_ENTRIES + 3 {
+            history.push(&format!("aoc {}", i));
+        }
+        history.push("aoc 34");
+        let lines = history.lines();
+        assert_eq!(lines.len(), HISTORY_ENTRIES);
+        assert_eq!(lines[0], "   4  aoc 3");
+        history.push(&"x".repeat(LIMIT - 10));
+        assert_eq!(history.lines().len(), 2);
+        assert_eq!(history.expand("!!").unwrap().unwrap().len(), LIMIT - 10);
+        history.push("aoc 35");
+        history.set_limit(100);
+        assert_eq!(history.lines(), ["  37  aoc 35"]);
+        history.push(&"x".repeat(101));
+        assert_eq!(history.lines().len(), 1);
+    }
+}
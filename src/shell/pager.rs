@@ -0,0 +1,122 @@
+use super::{Marker, TerminalSize};
+use alloc::string::String;
+
+/// Terminal rows taken by the output of a command line ending with `more`, which pauses every screenful
+///
+/// Nothing is kept but the line that did not fit: the commands are not polled while the pager waits
+/// for a key, so their output is only produced as it is read. They also wait a little for the terminal
+/// to report its size, the pager going on with the size known before for terminals that never do.
+pub(super) struct Pager {
+    rows: usize,
+    cols: usize,
+    // size reported, or not coming
+    sized: bool,
+    // clock time of the size query
+    asked: Option<u64>,
+    // rows taken since the last key
+    shown: usize,
+    /// line waiting for a key, with its marker
+    pub(super) held: Option<(Marker, String)>,
+}
+
+/// Shown on a line of its own while paused
+pub(super) const MORE: &str = "--More--";
+/// Erase the `MORE` line and go back to the end of the output, at the start of its last line
+pub(super) const ERASE_MORE: &[u8] = b"\r\x1b[K\x1b[A";
+/// Milliseconds waited for the size of the terminal
+const SIZE_TIMEOUT: u64 = 200;
+
+impl Pager {
+    /// Paging with `size` until the terminal answers the size query sent along, at clock time `asked`
+    pub(super) fn new(size: TerminalSize, asked: Option<u64>) -> Self {
+        Self {
+            rows: usize::from(size.rows).max(2),
+            cols: usize::from(size.cols).max(1),
+            sized: false,
+            asked,
+            shown: 0,
+            held: None,
+        }
+    }
+
+    /// Size reported by the terminal
+    pub(super) fn resize(&mut self, size: TerminalSize) {
+        self.rows = usize::from(size.rows).max(2);
+        self.cols = usize::from(size.cols).max(1);
+        self.sized = true;
+    }
+
+    /// Whether the commands still wait for the size of the terminal, never without a clock
+    pub(super) fn probing(&mut self, clock: Option<fn() -> u64>) -> bool {
+        if !self.sized {
+            self.sized = clock.zip(self.asked).is_none_or(|(clock, asked)| clock() - asked >= SIZE_TIMEOUT);
+        }
+        !self.sized
+    }
+
+    /// Clock time at which the commands stop waiting for the size of the terminal
+    pub(super) fn deadline(&self) -> Option<u64> {
+        self.asked.map(|asked| asked + SIZE_TIMEOUT)
+    }
+
+    /// Whether the commands wait for a key
+    pub(super) fn waiting(&self) -> bool {
+        self.held.is_some()
+    }
+
+    /// Count the rows of a line `width` columns wide, false when it does not fit before the `MORE` line
+    pub(super) fn fits(&mut self, width: usize) -> bool {
+        let rows = self.rows_of(width);
+        // a line taller than the screen is shown after a key, rather than held forever
+        if self.shown > 0 && self.shown + rows > self.rows - 1 {
+            return false;
+        }
+        self.shown += rows;
+        true
+    }
+
+    /// After a key, the held line `width` columns wide is shown, followed by a screenful or by nothing
+    pub(super) fn resume(&mut self, screenful: bool, width: usize) {
+        self.shown = if screenful { self.rows_of(width) } else { self.rows - 1 };
+    }
+
+    fn rows_of(&self, width: usize) -> usize {
+        width.div_ceil(self.cols).max(1)
+    }
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pager() {
+        let mut pager = Pager::new(TerminalSize::default(), Some(0));
+        assert!(pager.probing(Some(|| 100)));
+        pager.resize(TerminalSize { rows: 4, cols: 10 });
+        assert!(!pager.probing(Some(|| 100)));
+        assert!(!pager.waiting());
+        assert!(pager.fits(0));
+        assert!(pager.fits(15));
+        assert!(!pager.fits(3));
+        pager.resume(false, 3);
+        assert!(!pager.fits(3));
+        pager.resume(true, 3);
+        assert!(pager.fits(12));
+        assert!(!pager.fits(1));
+        // taller than the screen
+        pager.resume(true, 100);
+        assert!(!pager.fits(1));
+        pager.resume(true, 1);
+        assert!(pager.fits(1));
+    }
+
+    #[test]
+    fn test_pager_unsized() {
+        let mut pager = Pager::new(TerminalSize::default(), Some(0));
+        assert!(pager.probing(Some(|| 199)));
+        assert!(!pager.probing(Some(|| 200)));
+        assert!(!pager.probing(Some(|| 100)));
+        assert!(!Pager::new(TerminalSize::default(), None).probing(None));
+    }
+}
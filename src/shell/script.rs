@@ -0,0 +1,78 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Command lines run one after the other, with the input lines of the commands reading some
+pub(super) struct Script {
+    lines: VecDeque<String>,
+    /// started with `source` at the prompt, shown again at the end
+    pub(super) interactive: bool,
+}
+
+impl Script {
+    pub(super) fn new(lines: Vec<String>, interactive: bool) -> Self {
+        Self {
+            lines: lines.into(),
+            interactive,
+        }
+    }
+
+    pub(super) fn from_bytes(bytes: &[u8]) -> Self {
+        Self::new(lines(bytes), false)
+    }
+
+    /// Next command line, skipping blank lines and `#` comments
+    pub(super) fn next_command(&mut self) -> Option<String> {
+        loop {
+            let line = self.lines.pop_front()?;
+            let trimmed = line.trim_start();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                return Some(line);
+            }
+        }
+    }
+
+    /// Input lines of a command: up to the terminator for a heredoc, the rest of the script otherwise
+    pub(super) fn input(&mut self, terminator: Option<&str>) -> Vec<String> {
+        let Some(terminator) = terminator else {
+            return self.lines.drain(..).collect();
+        };
+        let mut input = Vec::new();
+        while let Some(line) = self.lines.pop_front() {
+            if line == terminator {
+                break;
+            }
+            input.push(line);
+        }
+        input
+    }
+}
+
+/// Lines of a byte stream, ended by `\n` or `\r\n`, invalid UTF-8 being replaced
+pub(super) fn lines(bytes: &[u8]) -> Vec<String> {
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    bytes
+        .split(|&b| b == b'\n')
+        .map(|line| String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned())
+        .collect()
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let mut script = Script::from_bytes(b"# comment\r\n\naoc 1 <<END\r\n1\n\nEND\ncat <\na\nb\n");
+        assert_eq!(script.next_command().as_deref(), Some("aoc 1 <<END"));
+        assert_eq!(script.input(Some("END")), ["1", ""]);
+        assert_eq!(script.next_command().as_deref(), Some("cat <"));
+        assert_eq!(script.input(None), ["a", "b"]);
+        assert_eq!(script.next_command(), None);
+        assert_eq!(lines(b""), Vec::<String>::new());
+        assert_eq!(lines(b"\n"), [""]);
+    }
+}
@@ -0,0 +1,177 @@
+//! Display width and grapheme clusters, close enough to what terminals do without the Unicode tables
+//!
+//! Combining marks, joiners and variation selectors take no column and stay with the character before
+//! them; East Asian wide characters and emoji take two columns.
+
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036f),
+    (0x0483, 0x0489),
+    (0x0591, 0x05bd),
+    (0x0610, 0x061a),
+    (0x064b, 0x065f),
+    (0x0670, 0x0670),
+    (0x06d6, 0x06dc),
+    (0x0e31, 0x0e31),
+    (0x0e34, 0x0e3a),
+    (0x0e47, 0x0e4e),
+    (0x1ab0, 0x1aff),
+    (0x1dc0, 0x1dff),
+    (0x200b, 0x200f),
+    (0x20d0, 0x20ff),
+    (0xfe00, 0xfe0f),
+    (0xfe20, 0xfe2f),
+    (0x1f3fb, 0x1f3ff),
+    (0xe0020, 0xe007f),
+    (0xe0100, 0xe01ef),
+];
+
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115f),
+    (0x231a, 0x231b),
+    (0x23e9, 0x23ec),
+    (0x25fd, 0x25fe),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x26aa, 0x26ab),
+    (0x26bd, 0x26be),
+    (0x26c4, 0x26c5),
+    (0x2705, 0x2705),
+    (0x270a, 0x270b),
+    (0x2728, 0x2728),
+    (0x274c, 0x274c),
+    (0x2753, 0x2757),
+    (0x2795, 0x2797),
+    (0x2b1b, 0x2b1c),
+    (0x2b50, 0x2b55),
+    (0x2e80, 0x303e),
+    (0x3041, 0x33ff),
+    (0x3400, 0x4dbf),
+    (0x4e00, 0x9fff),
+    (0xa000, 0xa4cf),
+    (0xac00, 0xd7a3),
+    (0xf900, 0xfaff),
+    (0xfe30, 0xfe4f),
+    (0xff00, 0xff60),
+    (0xffe0, 0xffe6),
+    (0x1f004, 0x1f004),
+    (0x1f1e6, 0x1f1ff),
+    (0x1f200, 0x1f251),
+    (0x1f300, 0x1f64f),
+    (0x1f680, 0x1f6ff),
+    (0x1f7e0, 0x1f7eb),
+    (0x1f90c, 0x1f9ff),
+    (0x1fa70, 0x1faff),
+    (0x20000, 0x2fffd),
+    (0x30000, 0x3fffd),
+];
+
+const ZWJ: char = '\u{200d}';
+
+fn in_table(table: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    table
+        .binary_search_by(|&(start, end)| match (start > c, end < c) {
+            (true, _) => core::cmp::Ordering::Greater,
+            (_, true) => core::cmp::Ordering::Less,
+            _ => core::cmp::Ordering::Equal,
+        })
+        .is_ok()
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1f1e6}'..='\u{1f1ff}')
+}
+
+/// Columns taken by a character, 0 for control characters
+pub fn char_width(c: char) -> usize {
+    if c.is_control() || in_table(ZERO_WIDTH, c) {
+        0
+    } else if in_table(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Columns taken by a text, each grapheme cluster counting as its first character
+pub fn width(s: &str) -> usize {
+    graphemes(s).map(|g| g.chars().next().map_or(0, char_width)).sum()
+}
+
+/// Byte offset of the end of the grapheme cluster starting at `pos`
+pub fn next_boundary(s: &str, pos: usize) -> usize {
+    let mut chars = s[pos..].char_indices().peekable();
+    let Some((_, first)) = chars.next() else {
+        return pos;
+    };
+    let mut end = pos + first.len_utf8();
+    let mut joined = false;
+    let mut flag = is_regional_indicator(first);
+    while let Some(&(i, c)) = chars.peek() {
+        // the second half of a flag, a mark, or a character after a joiner
+        if (flag && is_regional_indicator(c)) || c == ZWJ || in_table(ZERO_WIDTH, c) || joined {
+            joined = c == ZWJ;
+            flag = false;
+            end = pos + i + c.len_utf8();
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    end
+}
+
+/// Byte offset of the start of the grapheme cluster ending at `pos`
+pub fn prev_boundary(s: &str, pos: usize) -> usize {
+    let mut start = 0;
+    while start < pos {
+        let end = next_boundary(s, start);
+        if end >= pos {
+            break;
+        }
+        start = end;
+    }
+    start
+}
+
+fn graphemes(s: &str) -> impl Iterator<Item = &str> {
+    let mut start = 0;
+    core::iter::from_fn(move || {
+        let end = next_boundary(s, start);
+        let grapheme = &s[start..end];
+        start = end;
+        (!grapheme.is_empty()).then_some(grapheme)
+    })
+}
+
+#[cfg(all(target_os = "linux", test))]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width("abc"), 3);
+        assert_eq!(width("日本"), 4);
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(width("\u{1f44d}\u{1f3fd}"), 2);
+        assert_eq!(width("\u{1f1eb}\u{1f1f7}"), 2);
+        assert_eq!(width("\u{fffd}"), 1);
+        assert_eq!(char_width('\x1b'), 0);
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let s = "ae\u{301}\u{1f468}\u{200d}\u{1f467}\u{1f1eb}\u{1f1f7}\u{1f1e9}x";
+        let graphemes: Vec<&str> = graphemes(s).collect();
+        assert_eq!(
+            graphemes,
+            ["a", "e\u{301}", "\u{1f468}\u{200d}\u{1f467}", "\u{1f1eb}\u{1f1f7}", "\u{1f1e9}", "x"]
+        );
+        assert_eq!(next_boundary(s, 1), 4);
+        assert_eq!(prev_boundary(s, 4), 1);
+        assert_eq!(prev_boundary(s, s.len()), s.len() - 1);
+        assert_eq!(prev_boundary(s, 0), 0);
+        assert_eq!(next_boundary(s, s.len()), s.len());
+    }
+}